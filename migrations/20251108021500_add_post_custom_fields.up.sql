@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- 自定义字段：JSON 对象，形如 {"difficulty":"hard","repo_url":"https://..."}
+ALTER TABLE posts ADD COLUMN custom_fields TEXT NOT NULL DEFAULT '{}';
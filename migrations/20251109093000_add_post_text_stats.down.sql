@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE posts DROP COLUMN auto_excerpt;
+ALTER TABLE posts DROP COLUMN reading_minutes;
+ALTER TABLE posts DROP COLUMN word_count;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- 保存时计算：字数、预计阅读分钟数、自动摘要（未填写 excerpt 时对外展示）
+ALTER TABLE posts ADD COLUMN word_count      INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE posts ADD COLUMN reading_minutes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE posts ADD COLUMN auto_excerpt    TEXT;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- 渲染时生成的目录：[{ "level": 2, "text": "...", "anchor": "..." }]
+ALTER TABLE posts ADD COLUMN toc TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,45 @@
+-- Add down migration script here
+-- protected 文章降级为 private，恢复 CHECK 约束并去掉 password_hash
+DROP TRIGGER IF EXISTS posts_visibility_insert;
+DROP TRIGGER IF EXISTS posts_visibility_update;
+
+CREATE TABLE posts_old (
+  id              TEXT PRIMARY KEY,
+  slug            TEXT NOT NULL UNIQUE,
+  title           TEXT NOT NULL,
+  excerpt         TEXT,
+  body_md         TEXT NOT NULL,
+  body_html       TEXT NOT NULL,
+  status          TEXT NOT NULL CHECK(status IN ('draft','published')),
+  visibility      TEXT NOT NULL CHECK(visibility IN ('public','private')) DEFAULT 'public',
+  author_id       TEXT NOT NULL,
+  published_at    TEXT,
+  created_at      TEXT NOT NULL,
+  updated_at      TEXT NOT NULL,
+  custom_fields   TEXT NOT NULL DEFAULT '{}',
+  word_count      INTEGER NOT NULL DEFAULT 0,
+  reading_minutes INTEGER NOT NULL DEFAULT 0,
+  auto_excerpt    TEXT,
+  toc             TEXT NOT NULL DEFAULT '[]'
+);
+
+INSERT INTO posts_old (
+  id, slug, title, excerpt, body_md, body_html, status, visibility, author_id, published_at,
+  created_at, updated_at, custom_fields, word_count, reading_minutes, auto_excerpt, toc
+)
+SELECT
+  id, slug, title, excerpt, body_md, body_html, status,
+  CASE WHEN visibility = 'public' THEN 'public' ELSE 'private' END,
+  author_id, published_at,
+  created_at, updated_at, custom_fields, word_count, reading_minutes, auto_excerpt, toc
+FROM posts;
+
+CREATE TEMP TABLE post_tags_bak AS SELECT post_id, tag_id FROM post_tags;
+DROP TABLE posts;
+ALTER TABLE posts_old RENAME TO posts;
+INSERT INTO post_tags (post_id, tag_id) SELECT post_id, tag_id FROM post_tags_bak;
+DROP TABLE post_tags_bak;
+
+CREATE INDEX IF NOT EXISTS idx_posts_status_visibility_pubat
+  ON posts(status, visibility, published_at DESC);
+CREATE INDEX IF NOT EXISTS idx_posts_slug ON posts(slug);
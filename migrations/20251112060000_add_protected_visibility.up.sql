@@ -0,0 +1,56 @@
+-- Add up migration script here
+-- 新增 protected 可见性（需密码访问）与 posts.password_hash。
+-- SQLite 不能修改 CHECK 约束，只能重建表；这里顺便把 visibility 的取值校验改为触发器，
+-- 以后增删取值只需重建触发器。
+-- 迁移运行在事务中且外键开启，DROP TABLE posts 会级联清空 post_tags，故先备份再恢复。
+CREATE TABLE posts_new (
+  id              TEXT PRIMARY KEY,
+  slug            TEXT NOT NULL UNIQUE,
+  title           TEXT NOT NULL,
+  excerpt         TEXT,
+  body_md         TEXT NOT NULL,
+  body_html       TEXT NOT NULL,
+  status          TEXT NOT NULL CHECK(status IN ('draft','published')),
+  visibility      TEXT NOT NULL DEFAULT 'public',
+  author_id       TEXT NOT NULL,
+  published_at    TEXT,
+  created_at      TEXT NOT NULL,
+  updated_at      TEXT NOT NULL,
+  custom_fields   TEXT NOT NULL DEFAULT '{}',
+  word_count      INTEGER NOT NULL DEFAULT 0,
+  reading_minutes INTEGER NOT NULL DEFAULT 0,
+  auto_excerpt    TEXT,
+  toc             TEXT NOT NULL DEFAULT '[]',
+  password_hash   TEXT
+);
+
+INSERT INTO posts_new (
+  id, slug, title, excerpt, body_md, body_html, status, visibility, author_id, published_at,
+  created_at, updated_at, custom_fields, word_count, reading_minutes, auto_excerpt, toc
+)
+SELECT
+  id, slug, title, excerpt, body_md, body_html, status, visibility, author_id, published_at,
+  created_at, updated_at, custom_fields, word_count, reading_minutes, auto_excerpt, toc
+FROM posts;
+
+CREATE TEMP TABLE post_tags_bak AS SELECT post_id, tag_id FROM post_tags;
+DROP TABLE posts;
+ALTER TABLE posts_new RENAME TO posts;
+INSERT INTO post_tags (post_id, tag_id) SELECT post_id, tag_id FROM post_tags_bak;
+DROP TABLE post_tags_bak;
+
+CREATE INDEX IF NOT EXISTS idx_posts_status_visibility_pubat
+  ON posts(status, visibility, published_at DESC);
+CREATE INDEX IF NOT EXISTS idx_posts_slug ON posts(slug);
+
+CREATE TRIGGER posts_visibility_insert BEFORE INSERT ON posts
+WHEN NEW.visibility NOT IN ('public','private','protected')
+BEGIN
+  SELECT RAISE(ABORT, 'invalid visibility');
+END;
+
+CREATE TRIGGER posts_visibility_update BEFORE UPDATE OF visibility ON posts
+WHEN NEW.visibility NOT IN ('public','private','protected')
+BEGIN
+  SELECT RAISE(ABORT, 'invalid visibility');
+END;
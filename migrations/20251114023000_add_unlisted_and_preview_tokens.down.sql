@@ -0,0 +1,19 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS preview_tokens;
+
+UPDATE posts SET visibility = 'private' WHERE visibility = 'unlisted';
+
+DROP TRIGGER IF EXISTS posts_visibility_insert;
+DROP TRIGGER IF EXISTS posts_visibility_update;
+
+CREATE TRIGGER posts_visibility_insert BEFORE INSERT ON posts
+WHEN NEW.visibility NOT IN ('public','private','protected')
+BEGIN
+  SELECT RAISE(ABORT, 'invalid visibility');
+END;
+
+CREATE TRIGGER posts_visibility_update BEFORE UPDATE OF visibility ON posts
+WHEN NEW.visibility NOT IN ('public','private','protected')
+BEGIN
+  SELECT RAISE(ABORT, 'invalid visibility');
+END;
@@ -0,0 +1,29 @@
+-- Add up migration script here
+-- 新增 unlisted 可见性：知道链接即可访问，但不出现在列表 / 标签 / RSS / sitemap
+DROP TRIGGER IF EXISTS posts_visibility_insert;
+DROP TRIGGER IF EXISTS posts_visibility_update;
+
+CREATE TRIGGER posts_visibility_insert BEFORE INSERT ON posts
+WHEN NEW.visibility NOT IN ('public','private','protected','unlisted')
+BEGIN
+  SELECT RAISE(ABORT, 'invalid visibility');
+END;
+
+CREATE TRIGGER posts_visibility_update BEFORE UPDATE OF visibility ON posts
+WHEN NEW.visibility NOT IN ('public','private','protected','unlisted')
+BEGIN
+  SELECT RAISE(ABORT, 'invalid visibility');
+END;
+
+-- 草稿预览链接：持有 token 即可免登录查看，可过期、可撤销
+CREATE TABLE IF NOT EXISTS preview_tokens(
+  id          TEXT PRIMARY KEY,
+  token       TEXT NOT NULL UNIQUE,
+  post_id     TEXT NOT NULL,
+  created_by  TEXT NOT NULL,
+  created_at  TEXT NOT NULL,
+  expires_at  TEXT NOT NULL,
+  revoked_at  TEXT,
+  FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_preview_tokens_post ON preview_tokens(post_id);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_posts_lang;
+DROP INDEX IF EXISTS idx_posts_translation_group;
+ALTER TABLE posts DROP COLUMN translation_group;
+ALTER TABLE posts DROP COLUMN lang;
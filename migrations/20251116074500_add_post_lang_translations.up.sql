@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- 多语言：每篇文章一个语言；同一 translation_group 的文章互为译文
+ALTER TABLE posts ADD COLUMN lang TEXT NOT NULL DEFAULT 'zh';
+ALTER TABLE posts ADD COLUMN translation_group TEXT;
+CREATE INDEX IF NOT EXISTS idx_posts_translation_group ON posts(translation_group);
+CREATE INDEX IF NOT EXISTS idx_posts_lang ON posts(lang);
@@ -0,0 +1,10 @@
+-- 外部平台导入映射：同一来源的同一条目重复导入时更新原文章而不是新建
+CREATE TABLE IF NOT EXISTS imported_items(
+  source      TEXT NOT NULL,  -- 例如 wordpress:https://old.example.com
+  source_id   TEXT NOT NULL,  -- 原平台的文章 id
+  post_id     TEXT NOT NULL,
+  imported_at TEXT NOT NULL,
+  PRIMARY KEY(source, source_id),
+  FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_imported_items_post ON imported_items(post_id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE posts DROP COLUMN render_options;
+ALTER TABLE posts DROP COLUMN markdown_options;
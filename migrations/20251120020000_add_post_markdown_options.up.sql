@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Markdown 渲染选项：markdown_options 为单篇覆盖（JSON 对象，NULL 表示全部用站点默认）
+-- render_options 为上次渲染时实际生效的完整选项，便于按原样重新渲染（NULL 表示记录之前渲染的）
+ALTER TABLE posts ADD COLUMN markdown_options TEXT;
+ALTER TABLE posts ADD COLUMN render_options TEXT;
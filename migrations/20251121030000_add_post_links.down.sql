@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_post_links_target_slug;
+DROP INDEX IF EXISTS idx_post_links_target_id;
+DROP TABLE IF EXISTS post_links;
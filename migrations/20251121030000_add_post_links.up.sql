@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- 站内 wiki 链接图：source 正文里的 [[target]]；target_id 为保存时解析到的文章（NULL 表示未找到）
+CREATE TABLE IF NOT EXISTS post_links(
+  source_id   TEXT NOT NULL,
+  target_slug TEXT NOT NULL,
+  target_id   TEXT,
+  PRIMARY KEY(source_id, target_slug),
+  FOREIGN KEY(source_id) REFERENCES posts(id) ON DELETE CASCADE,
+  FOREIGN KEY(target_id) REFERENCES posts(id) ON DELETE SET NULL
+);
+CREATE INDEX IF NOT EXISTS idx_post_links_target_id ON post_links(target_id);
+CREATE INDEX IF NOT EXISTS idx_post_links_target_slug ON post_links(target_slug);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_posts_render_version;
+ALTER TABLE posts DROP COLUMN render_version;
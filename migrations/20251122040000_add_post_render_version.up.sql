@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- 渲染管线版本：低于 markdown::RENDERER_VERSION 的文章由 rerender 重新生成 body_html 等派生字段
+-- 已有文章记为 0，升级后跑一次 rerender 即可
+ALTER TABLE posts ADD COLUMN render_version INTEGER NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS idx_posts_render_version ON posts(render_version);
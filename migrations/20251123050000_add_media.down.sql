@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_media_hash;
+DROP TABLE IF EXISTS media;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- 上传文件按内容哈希存放（uploads/ab/cd/<sha256>.<ext>），这里记录谁上传了哪个文件、原始文件名
+-- 同一用户重复上传同样内容只记一条；不同用户共享同一份文件
+CREATE TABLE IF NOT EXISTS media(
+  id         TEXT PRIMARY KEY,
+  owner_id   TEXT NOT NULL,
+  hash       TEXT NOT NULL,
+  ext        TEXT NOT NULL,
+  filename   TEXT NOT NULL,
+  size       INTEGER NOT NULL,
+  created_at TEXT NOT NULL,
+  UNIQUE(owner_id, hash),
+  FOREIGN KEY(owner_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_media_hash ON media(hash);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_post_media_hash;
+DROP TABLE IF EXISTS post_media;
+DROP INDEX IF EXISTS idx_media_owner_created;
+ALTER TABLE media DROP COLUMN alt;
+ALTER TABLE media DROP COLUMN height;
+ALTER TABLE media DROP COLUMN width;
+ALTER TABLE media DROP COLUMN mime;
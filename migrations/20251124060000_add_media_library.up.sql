@@ -0,0 +1,34 @@
+-- Add up migration script here
+-- 媒体库：类型、尺寸（图片）、替代文字
+ALTER TABLE media ADD COLUMN mime TEXT NOT NULL DEFAULT 'application/octet-stream';
+ALTER TABLE media ADD COLUMN width INTEGER;
+ALTER TABLE media ADD COLUMN height INTEGER;
+ALTER TABLE media ADD COLUMN alt TEXT NOT NULL DEFAULT '';
+UPDATE media SET mime = CASE ext
+  WHEN 'jpg'  THEN 'image/jpeg'
+  WHEN 'png'  THEN 'image/png'
+  WHEN 'gif'  THEN 'image/gif'
+  WHEN 'webp' THEN 'image/webp'
+  WHEN 'avif' THEN 'image/avif'
+  WHEN 'svg'  THEN 'image/svg+xml'
+  WHEN 'pdf'  THEN 'application/pdf'
+  WHEN 'mp3'  THEN 'audio/mpeg'
+  WHEN 'm4a'  THEN 'audio/mp4'
+  WHEN 'ogg'  THEN 'audio/ogg'
+  WHEN 'wav'  THEN 'audio/wav'
+  WHEN 'flac' THEN 'audio/flac'
+  WHEN 'mp4'  THEN 'video/mp4'
+  WHEN 'webm' THEN 'video/webm'
+  ELSE mime END;
+CREATE INDEX IF NOT EXISTS idx_media_owner_created ON media(owner_id, created_at);
+
+-- 文章正文引用了哪些上传文件（按内容哈希），保存文章时整体重建；删除媒体前据此检查
+CREATE TABLE IF NOT EXISTS post_media(
+  post_id TEXT NOT NULL,
+  hash    TEXT NOT NULL,
+  PRIMARY KEY(post_id, hash),
+  FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_post_media_hash ON post_media(hash);
+-- 已有文章的引用由 rerender 补上
+UPDATE posts SET render_version = 0;
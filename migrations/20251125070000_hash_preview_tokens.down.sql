@@ -0,0 +1,6 @@
+-- Add down migration script here
+-- 哈希还原不出 token，旧链接全部作废
+UPDATE preview_tokens
+   SET token_hash = 'revoked:' || id,
+       revoked_at = COALESCE(revoked_at, strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now'));
+ALTER TABLE preview_tokens RENAME COLUMN token_hash TO token;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- 预览链接只存 token 的 sha256：库或备份泄露时拿不到可用的链接
+-- 已有的明文 token 没法在 SQL 里算哈希，一律撤销（需要的话重新生成链接）
+ALTER TABLE preview_tokens RENAME COLUMN token TO token_hash;
+UPDATE preview_tokens
+   SET token_hash = 'revoked:' || id,
+       revoked_at = COALESCE(revoked_at, strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now'));
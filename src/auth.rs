@@ -4,17 +4,32 @@ use axum_extra::{TypedHeader, headers::{Authorization, authorization::Bearer}};
 use jsonwebtoken::{encode, decode, EncodingKey, DecodingKey, Header, Validation, Algorithm};
 use serde::{Serialize, Deserialize};
 use chrono::{Utc, Duration};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, password_hash::SaltString};
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
 use crate::{error::AppError, state::AppState};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
-    pub sub: String, // user id
+    pub sub: String, // user id；scope 不为空时是对应资源的 id
     pub exp: usize,
+    // 受限令牌的作用域（如 "post"）；登录令牌没有该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
 }
 
 pub fn sign(sub: &str, secret: &str, ttl_minutes: i64) -> anyhow::Result<String> {
+    encode_claims(sub, None, secret, ttl_minutes)
+}
+
+/// 签发只对某个资源有效的短期令牌（不能当作登录令牌使用）
+pub fn sign_scoped(sub: &str, scope: &str, secret: &str, ttl_minutes: i64) -> anyhow::Result<String> {
+    encode_claims(sub, Some(scope), secret, ttl_minutes)
+}
+
+fn encode_claims(sub: &str, scope: Option<&str>, secret: &str, ttl_minutes: i64) -> anyhow::Result<String> {
     let exp = (Utc::now() + Duration::minutes(ttl_minutes)).timestamp() as usize;
-    let claims = Claims { sub: sub.into(), exp };
+    let claims = Claims { sub: sub.into(), exp, scope: scope.map(Into::into) };
     Ok(encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(secret.as_bytes()))?)
 }
 
@@ -35,6 +50,45 @@ impl FromRequestParts<AppState> for AuthUser {
                 .await
                 .map_err(|_| AppError::Unauthorized)?;
         let claims = verify(bearer.token(), &app.jwt_secret).map_err(|_| AppError::Unauthorized)?;
+        if claims.scope.is_some() {
+            return Err(AppError::Unauthorized);
+        }
         Ok(AuthUser { user_id: claims.sub })
     }
-}
\ No newline at end of file
+}
+
+/// 校验受限令牌：签名有效、未过期、作用域与资源 id 均匹配
+pub fn verify_scoped(token: &str, scope: &str, sub: &str, secret: &str) -> bool {
+    verify(token, secret)
+        .map(|c| c.scope.as_deref() == Some(scope) && c.sub == sub)
+        .unwrap_or(false)
+}
+
+/// 库里保存的秘密链接 token（如草稿预览）的摘要；token 本身是高熵随机串，不需要加盐慢哈希
+pub fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?
+        .to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false)
+}
+/// 是否为站点管理员（用户名在 ADMIN_USERS 中）
+pub async fn is_admin(app: &AppState, user_id: &str) -> Result<bool, AppError> {
+    if app.cfg.admin_users.is_empty() {
+        return Ok(false);
+    }
+    let name = sqlx::query_scalar!("SELECT username FROM users WHERE id = ?", user_id)
+        .fetch_optional(&app.db)
+        .await?;
+    Ok(name.is_some_and(|n| app.cfg.admin_users.contains(&n)))
+}
@@ -0,0 +1,308 @@
+// 逻辑备份：整库导出为 JSON Lines，恢复到空库
+// 第一行是头（格式版本、schema 版本），之后每行一条记录 {"table": ..., "row": {...}}
+// 表按 sqlite_master 枚举，以后新增的表自动包含在内；BLOB 列写成 {"$hex": "..."}
+use std::io::BufRead;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{Column, Row, Sqlite, Transaction, TypeInfo, ValueRef};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{db::Db, models::now};
+
+pub const FORMAT: &str = "blog-backup";
+pub const FORMAT_VERSION: i64 = 1;
+
+// 不导出密码哈希时写入的占位值：不是合法的 PHC 串，恢复后需要重置密码
+const REDACTED_HASH: &str = "!";
+
+#[derive(Serialize, Deserialize)]
+pub struct Header {
+    pub format: String,
+    pub version: i64,
+    pub schema_version: i64, // 最后一个已执行迁移的版本号
+    pub created_at: String,
+    pub password_hashes: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    table: String,
+    row: Map<String, Value>,
+}
+
+/// 每张表的行数
+pub type Counts = Vec<(String, u64)>;
+
+pub async fn schema_version(db: &Db) -> anyhow::Result<i64> {
+    // _sqlx_migrations 由迁移器运行时创建，不走编译期检查
+    let v: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(db)
+            .await?;
+    Ok(v.unwrap_or(0))
+}
+
+async fn tables(tx: &mut Transaction<'_, Sqlite>) -> anyhow::Result<Vec<String>> {
+    Ok(sqlx::query_scalar::<_, String>(
+        "SELECT name FROM sqlite_master
+          WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != '_sqlx_migrations'
+          ORDER BY name",
+    )
+    .fetch_all(&mut **tx)
+    .await?)
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// 导出到 out（逐行写出，可以直接接到下载响应上）；同一个读事务内完成，得到一致的快照
+pub async fn dump(
+    db: &Db,
+    out: &mut (impl AsyncWrite + Unpin),
+    password_hashes: bool,
+) -> anyhow::Result<Counts> {
+    let header = Header {
+        format: FORMAT.into(),
+        version: FORMAT_VERSION,
+        schema_version: schema_version(db).await?,
+        created_at: now(),
+        password_hashes,
+    };
+    let mut line = serde_json::to_vec(&header)?;
+    line.push(b'\n');
+    out.write_all(&line).await?;
+
+    let mut tx = db.begin().await?;
+    let mut counts = vec![];
+    for table in tables(&mut tx).await? {
+        let rows = sqlx::query(&format!("SELECT * FROM {} ORDER BY rowid", quote(&table)))
+            .fetch_all(&mut *tx)
+            .await?;
+        for r in &rows {
+            let mut row = Map::new();
+            for (i, col) in r.columns().iter().enumerate() {
+                row.insert(col.name().to_string(), column_value(r, i)?);
+            }
+            if table == "users" && !password_hashes {
+                row.insert("password_hash".into(), REDACTED_HASH.into());
+            }
+            let mut line = serde_json::to_vec(&Record { table: table.clone(), row })?;
+            line.push(b'\n');
+            out.write_all(&line).await?;
+        }
+        counts.push((table, rows.len() as u64));
+    }
+    tx.commit().await?;
+    out.flush().await?;
+    Ok(counts)
+}
+
+fn column_value(r: &sqlx::sqlite::SqliteRow, i: usize) -> anyhow::Result<Value> {
+    let raw = r.try_get_raw(i)?;
+    if raw.is_null() {
+        return Ok(Value::Null);
+    }
+    Ok(match raw.type_info().name() {
+        "INTEGER" => Value::from(r.try_get::<i64, _>(i)?),
+        "REAL" => Value::from(r.try_get::<f64, _>(i)?),
+        "BLOB" => {
+            let hex: String = r.try_get::<Vec<u8>, _>(i)?.iter().map(|b| format!("{:02x}", b)).collect();
+            serde_json::json!({ "$hex": hex })
+        }
+        _ => Value::from(r.try_get::<String, _>(i)?),
+    })
+}
+
+/// 恢复到空库（schema 已由迁移建好）：校验头与 schema 版本，全部记录在一个事务里写入，任一失败整体回滚
+pub async fn restore(db: &Db, input: impl BufRead) -> anyhow::Result<Counts> {
+    let mut lines = input.lines();
+    let first = lines.next().transpose()?.unwrap_or_default();
+    let header: Header = serde_json::from_str(&first)
+        .map_err(|e| anyhow::anyhow!("invalid backup header: {}", e))?;
+    if header.format != FORMAT || header.version != FORMAT_VERSION {
+        anyhow::bail!("unsupported backup format {} v{}", header.format, header.version);
+    }
+    let current = schema_version(db).await?;
+    if header.schema_version != current {
+        anyhow::bail!(
+            "schema version mismatch: backup {} vs database {} (restore with the release that wrote the backup)",
+            header.schema_version,
+            current
+        );
+    }
+
+    let mut tx = db.begin().await?;
+    let tables = tables(&mut tx).await?;
+    for t in &tables {
+        let n: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", quote(t)))
+            .fetch_one(&mut *tx)
+            .await?;
+        if n > 0 {
+            anyhow::bail!("database is not empty: table `{}` has {} rows", t, n);
+        }
+    }
+    // 外键检查推迟到提交时，记录顺序无关
+    sqlx::query("PRAGMA defer_foreign_keys = ON").execute(&mut *tx).await?;
+
+    let mut columns: std::collections::HashMap<String, Vec<String>> = Default::default();
+    let mut counts: Counts = tables.iter().map(|t| (t.clone(), 0)).collect();
+    for (n, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let lineno = n + 2;
+        let rec: Record = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("line {}: {}", lineno, e))?;
+        let Some(pos) = tables.iter().position(|t| *t == rec.table) else {
+            anyhow::bail!("line {}: unknown table `{}`", lineno, rec.table);
+        };
+
+        if !columns.contains_key(&rec.table) {
+            let cols = sqlx::query(&format!("PRAGMA table_info({})", quote(&rec.table)))
+                .fetch_all(&mut *tx)
+                .await?
+                .iter()
+                .map(|r| r.get::<String, _>("name"))
+                .collect();
+            columns.insert(rec.table.clone(), cols);
+        }
+        let known = &columns[&rec.table];
+        if let Some(bad) = rec.row.keys().find(|k| !known.contains(k)) {
+            anyhow::bail!("line {}: unknown column `{}.{}`", lineno, rec.table, bad);
+        }
+
+        let cols: Vec<&String> = rec.row.keys().collect();
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(&rec.table),
+            cols.iter().map(|c| quote(c)).collect::<Vec<_>>().join(", "),
+            vec!["?"; cols.len()].join(", ")
+        );
+        let mut q = sqlx::query(&sql);
+        for v in rec.row.values() {
+            q = match v {
+                Value::Null => q.bind(None::<String>),
+                Value::Bool(b) => q.bind(*b as i64),
+                Value::Number(num) => match num.as_i64() {
+                    Some(i) => q.bind(i),
+                    None => q.bind(num.as_f64()),
+                },
+                Value::String(s) => q.bind(s.clone()),
+                Value::Object(o) if o.len() == 1 && o.get("$hex").is_some_and(Value::is_string) => {
+                    q.bind(from_hex(o["$hex"].as_str().unwrap_or_default())
+                        .ok_or_else(|| anyhow::anyhow!("line {}: invalid $hex value", lineno))?)
+                }
+                other => q.bind(other.to_string()),
+            };
+        }
+        q.execute(&mut *tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("line {}: {}", lineno, e))?;
+        counts[pos].1 += 1;
+    }
+
+    tx.commit().await?;
+    Ok(counts)
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    // 迁移里还没有 BLOB 列，另建一张表覆盖 BLOB / REAL / NULL
+    async fn db_with_extra() -> Db {
+        let db = testing::db().await;
+        sqlx::query("CREATE TABLE extra (id INTEGER PRIMARY KEY, data BLOB, score REAL, note TEXT)")
+            .execute(&db)
+            .await
+            .unwrap();
+        db
+    }
+
+    async fn dump_lines(db: &Db, password_hashes: bool) -> Vec<String> {
+        let mut out = vec![];
+        dump(db, &mut out, password_hashes).await.unwrap();
+        String::from_utf8(out).unwrap().lines().map(String::from).collect()
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let src = db_with_extra().await;
+        let cfg = testing::config();
+        let alice = testing::user(&src, "alice").await;
+        let body = serde_json::json!({ "title": "T", "slug": "t", "body_md": "# Hi\n\n[[t]]", "tags": ["a", "b"] });
+        testing::post(&src, &cfg, &alice, body).await.unwrap();
+        sqlx::query("INSERT INTO extra (id, data, score, note) VALUES (1, ?, 2.5, NULL), (2, NULL, NULL, 'x')")
+            .bind(vec![0u8, 1, 0x7f, 0xff])
+            .execute(&src)
+            .await
+            .unwrap();
+
+        let lines = dump_lines(&src, true).await;
+        let header: Header = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(header.schema_version, schema_version(&src).await.unwrap());
+        assert!(header.schema_version > 0);
+        let extra = r#"{"table":"extra","row":{"data":{"$hex":"00017fff"},"id":1,"note":null,"score":2.5}}"#;
+        assert!(lines.iter().any(|l| l == extra), "{:?}", lines);
+
+        let dst = db_with_extra().await;
+        let counts = restore(&dst, lines.join("\n").as_bytes()).await.unwrap();
+        let count = |t: &str| counts.iter().find(|(n, _)| n == t).unwrap().1;
+        assert_eq!((count("posts"), count("post_tags"), count("tags"), count("extra")), (1, 2, 2, 2));
+        // 再导出一遍，除了头里的时间以外逐行一致
+        assert_eq!(dump_lines(&dst, true).await[1..], lines[1..]);
+        let (blob, note): (Vec<u8>, Option<String>) = sqlx::query_as("SELECT data, note FROM extra WHERE id = 1")
+            .fetch_one(&dst)
+            .await
+            .unwrap();
+        assert_eq!((blob.as_slice(), note), (&[0, 1, 0x7f, 0xff][..], None));
+
+        // 只恢复到空库
+        let err = restore(&dst, lines.join("\n").as_bytes()).await.unwrap_err();
+        assert!(err.to_string().contains("not empty"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn rejects_other_schema_and_bad_rows() {
+        let src = testing::db().await;
+        testing::user(&src, "alice").await;
+        let lines = dump_lines(&src, false).await;
+        assert!(lines[1..].iter().any(|l| l.contains(r#""password_hash":"!""#)), "{:?}", lines);
+
+        let mut header: Header = serde_json::from_str(&lines[0]).unwrap();
+        header.schema_version -= 1;
+        let input = format!("{}\n{}", serde_json::to_string(&header).unwrap(), lines[1..].join("\n"));
+        let err = restore(&testing::db().await, input.as_bytes()).await.unwrap_err();
+        assert!(err.to_string().contains("schema version mismatch"), "{}", err);
+
+        for bad in [
+            r#"{"table":"nope","row":{}}"#,
+            r#"{"table":"users","row":{"id":"1","nope":1}}"#,
+            r#"{"table":"posts","row":{"id":"1"}}"#,
+        ] {
+            let dst = testing::db().await;
+            let input = format!("{}\n{}", lines.join("\n"), bad);
+            assert!(restore(&dst, input.as_bytes()).await.is_err(), "{}", bad);
+            // 前面的用户也一起回滚，库仍是空的
+            let n: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users").fetch_one(&dst).await.unwrap();
+            assert_eq!(n, 0);
+        }
+        assert!(restore(&testing::db().await, "not json".as_bytes()).await.is_err());
+        assert_eq!(from_hex("0g"), None);
+        assert_eq!(from_hex("abc"), None);
+    }
+}
@@ -0,0 +1,59 @@
+// src/bin/backup.rs
+// JSON Lines 逻辑备份 / 恢复（与 SQLite 文件拷贝互补：跨版本可读、可 diff）
+use anyhow::{bail, Context, Result};
+use blog_backend::{backup, config::Config, db};
+use std::{env, fs::File, io::BufReader};
+
+const USAGE: &str = "用法:
+  cargo run --bin backup -- dump <out.jsonl> [--with-password-hashes]
+  cargo run --bin backup -- restore <in.jsonl>
+  dump     默认不导出密码哈希（恢复后这些用户需要重置密码）
+  restore  只能恢复到空库（DATABASE_URL 指向新文件即可），schema 版本必须一致，全部在一个事务内完成";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (cmd, path, flags) = match args.as_slice() {
+        [cmd, path, flags @ ..] => (cmd.as_str(), path.as_str(), flags),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let cfg = Config::new()?;
+    let pool = db::init_pool(&cfg.database_url).await?;
+    db::migrate(&pool).await?;
+
+    let counts = match cmd {
+        "dump" => {
+            let mut hashes = false;
+            for f in flags {
+                match f.as_str() {
+                    "--with-password-hashes" => hashes = true,
+                    s => bail!("未知参数: {}\n{}", s, USAGE),
+                }
+            }
+            let file = tokio::fs::File::create(path).await.with_context(|| format!("无法创建 {}", path))?;
+            backup::dump(&pool, &mut tokio::io::BufWriter::new(file), hashes).await?
+        }
+        "restore" => {
+            if let Some(f) = flags.first() {
+                bail!("未知参数: {}\n{}", f, USAGE);
+            }
+            let input = BufReader::new(File::open(path).with_context(|| format!("无法读取 {}", path))?);
+            backup::restore(&pool, input).await?
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    for (table, n) in &counts {
+        println!("  {:<20} {}", table, n);
+    }
+    println!("OK: {} {}", if cmd == "dump" { "已导出到" } else { "已恢复自" }, path);
+    Ok(())
+}
@@ -0,0 +1,67 @@
+// src/bin/build_site.rs
+// 静态站生成：把已发布的 public 内容渲染到一个目录，供 Nginx / 对象存储等镜像托管
+use anyhow::{bail, Result};
+use blog_backend::{config::Config, db, ssg};
+use std::{env, path::PathBuf};
+
+const USAGE: &str = "用法: cargo run --bin build_site -- <out_dir> [--templates <dir>] [--title <name>] [--page-size <n>] [--full]
+  --templates <dir>  覆盖内置模板（base.html / index.html / post.html，同名文件优先）
+  --title <name>     站点标题（默认 Blog）
+  --page-size <n>    列表每页篇数（默认 10）
+  --full             忽略构建清单，全部重建（默认只重建页面内容变化的文章）";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let opts = match parse_args() {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let cfg = Config::new()?;
+    let pool = db::init_pool(&cfg.database_url).await?;
+    db::migrate(&pool).await?;
+
+    let r = ssg::build(&pool, &cfg, &opts).await?;
+    println!(
+        "OK: 文章 {} 篇重建、{} 篇未变、{} 篇移除；列表 {} 页；复制 {} 个上传文件 → {}",
+        r.rendered,
+        r.unchanged,
+        r.removed,
+        r.list_pages,
+        r.uploads,
+        opts.out.display()
+    );
+    Ok(())
+}
+
+fn parse_args() -> Result<ssg::Options> {
+    let mut it = env::args().skip(1);
+    let mut opts = ssg::Options {
+        out: PathBuf::new(),
+        templates: None,
+        title: "Blog".into(),
+        page_size: 10,
+        full: false,
+    };
+    let mut out = None;
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--templates" => opts.templates = it.next().map(PathBuf::from),
+            "--title" => opts.title = it.next().unwrap_or(opts.title),
+            "--page-size" => match it.next().and_then(|n| n.parse().ok()) {
+                Some(n) => opts.page_size = n,
+                None => bail!("--page-size 需要一个数字"),
+            },
+            "--full" => opts.full = true,
+            s if s.starts_with("--") => bail!("未知参数: {}", s),
+            _ => out = Some(PathBuf::from(a)),
+        }
+    }
+    let Some(out) = out else { bail!("缺少 <out_dir>") };
+    opts.out = out;
+    Ok(opts)
+}
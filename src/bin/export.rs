@@ -0,0 +1,87 @@
+// src/bin/export.rs
+// 把整站文章导出为 Markdown（YAML front matter）+ 引用到的上传文件，可用 import 命令原样导回
+use anyhow::{bail, Context, Result};
+use blog_backend::{config::Config, db, exporter};
+use std::{
+    env,
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+};
+
+const USAGE: &str = "用法: cargo run --bin export -- <out> [--author <username>]
+  <out>              以 .tar.gz / .tgz 结尾时打包成归档，否则写入该目录
+  --author <username> 只导出该作者的文章（默认全部）";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let (out, author) = match parse_args() {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let cfg = Config::new()?;
+    let pool = db::init_pool(&cfg.database_url).await?;
+    db::migrate(&pool).await?;
+
+    let author_id = match &author {
+        Some(name) => Some(
+            sqlx::query_scalar!(r#"SELECT id as "id!: String" FROM users WHERE username = ?"#, name)
+                .fetch_optional(&pool)
+                .await?
+                .with_context(|| format!("用户 `{}` 不存在", name))?,
+        ),
+        None => None,
+    };
+
+    let export = exporter::collect(&pool, author_id.as_deref()).await?;
+
+    let name = out.to_string_lossy();
+    let (export, missing) = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        let prefix = out
+            .file_name()
+            .and_then(|s| s.to_str())
+            .map(|s| s.trim_end_matches(".tar.gz").trim_end_matches(".tgz"))
+            .unwrap_or("blog-export")
+            .to_string();
+        let file = BufWriter::new(File::create(&out).with_context(|| format!("无法创建 {}", out.display()))?);
+        let storage = cfg.storage.clone();
+        tokio::task::spawn_blocking(move || {
+            let missing = exporter::write_tar_gz(&export, &*storage, &prefix, file)?;
+            anyhow::Ok((export, missing))
+        })
+        .await??
+    } else {
+        let missing = exporter::write_dir(&export, &*cfg.storage, &out).await?;
+        (export, missing)
+    };
+    for m in &missing {
+        println!("warning: upload not found: {}", m);
+    }
+
+    println!(
+        "OK: 已导出 {} 篇文章、{} 个上传文件到 {}",
+        export.posts.len(),
+        export.uploads.len() - missing.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+fn parse_args() -> Result<(PathBuf, Option<String>)> {
+    let mut it = env::args().skip(1);
+    let (mut out, mut author) = (None, None);
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--author" => author = it.next(),
+            s if s.starts_with("--") => bail!("未知参数: {}", s),
+            _ => out = Some(PathBuf::from(a)),
+        }
+    }
+    let Some(out) = out else { bail!("缺少 <out>") };
+    Ok((out, author))
+}
@@ -0,0 +1,425 @@
+// src/bin/import.rs
+// 从 Hugo / Jekyll / Hexo 的 Markdown 目录导入文章（YAML `---` 或 TOML `+++` front matter）
+// 默认只打印变更计划（dry run），确认无误后加 --apply 才真正写入
+use anyhow::{bail, Context, Result};
+use blog_backend::{config::Config, db, importer, models::PostInput, posts};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+const USAGE: &str = "用法: cargo run --bin import -- <dir> --author <username> [--static <dir>] [--overwrite] [--apply]
+  --author <username> 默认作者；front matter 里的 author 是已有用户时以它为准
+  --static <dir>  以 / 开头的图片路径相对的站点根目录（默认即 <dir>）
+  --overwrite     slug 已存在时用导入内容覆盖（默认跳过）
+  --apply         真正写入数据库并上传图片（默认只打印计划）";
+
+struct Args {
+    dir: PathBuf,
+    author: String,
+    static_root: PathBuf,
+    overwrite: bool,
+    apply: bool,
+}
+
+enum Action {
+    Create,
+    Update { id: String, changes: Vec<String> },
+    Unchanged,
+    Exists,
+}
+
+struct Doc {
+    file: PathBuf,
+    inp: PostInput,
+    ts: posts::Timestamps,
+    author: Option<String>, // front matter 里的作者用户名（导出包里有）
+    warnings: Vec<String>,
+    image_urls: Vec<String>, // 改写后引用的 /uploads/ 地址
+    action: Action,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let args = match parse_args() {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let cfg = Config::new()?;
+    let pool = db::init_pool(&cfg.database_url).await?;
+    db::migrate(&pool).await?;
+
+    let author_id = sqlx::query_scalar!(
+        r#"SELECT id as "id!: String" FROM users WHERE username = ?"#,
+        args.author
+    )
+    .fetch_optional(&pool)
+    .await?
+    .with_context(|| format!("用户 `{}` 不存在（先用 mkuser 创建）", args.author))?;
+
+    let mut files = vec![];
+    collect_markdown(&args.dir, &mut files)?;
+    files.sort();
+
+    // 1) 解析所有文件，生成计划
+    let mut docs: Vec<Doc> = vec![];
+    let mut uploads: Vec<importer::Upload> = vec![];
+    let mut images: HashMap<PathBuf, String> = HashMap::new(); // 源文件 → /uploads/ URL
+    let mut failed = 0;
+    let mut seen_slugs = HashSet::new();
+    let mut authors: HashMap<String, String> = HashMap::new(); // front matter 作者名 → 用户 id
+
+    for file in &files {
+        let res = load_doc(file, &args, &cfg, &mut images, &mut uploads);
+        let mut doc = match res {
+            Ok(d) => d,
+            Err(e) => {
+                println!("! error    {}  {:#}", file.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        let slug = doc.inp.slug.clone().unwrap_or_default();
+        if !seen_slugs.insert(slug.clone()) {
+            println!("! error    {}  duplicate slug `{}` in import set", file.display(), slug);
+            failed += 1;
+            continue;
+        }
+        if let Some(name) = doc.author.clone()
+            && !authors.contains_key(&name)
+        {
+            let id = sqlx::query_scalar!(
+                r#"SELECT id as "id!: String" FROM users WHERE username = ?"#,
+                name
+            )
+            .fetch_optional(&pool)
+            .await?;
+            match id {
+                Some(id) => {
+                    authors.insert(name, id);
+                }
+                None => doc.warnings.push(format!("author `{}` not found, using --author", name)),
+            }
+        }
+        doc.action = plan_action(&pool, &doc.inp, args.overwrite).await?;
+        docs.push(doc);
+    }
+
+    // 只上传会被写入的文章引用到的图片
+    let needed: HashSet<&str> = docs
+        .iter()
+        .filter(|d| matches!(d.action, Action::Create | Action::Update { .. }))
+        .flat_map(|d| d.image_urls.iter().map(String::as_str))
+        .collect();
+    uploads.retain(|u| needed.contains(u.url.as_str()));
+
+    // 2) 打印计划
+    let (mut n_create, mut n_update, mut n_same, mut n_exists) = (0, 0, 0, 0);
+    for d in &docs {
+        let slug = d.inp.slug.as_deref().unwrap_or_default();
+        match &d.action {
+            Action::Create => {
+                n_create += 1;
+                println!(
+                    "+ create   {:<32} \"{}\" [{} tags, {}]",
+                    slug,
+                    d.inp.title,
+                    d.inp.tags.as_ref().map_or(0, Vec::len),
+                    d.inp.status.as_deref().unwrap_or("draft")
+                );
+            }
+            Action::Update { changes, .. } => {
+                n_update += 1;
+                println!("~ update   {:<32} {}", slug, changes.join(", "));
+            }
+            Action::Unchanged => {
+                n_same += 1;
+                println!("= same     {}", slug);
+            }
+            Action::Exists => {
+                n_exists += 1;
+                println!("! exists   {:<32} (slug taken; use --overwrite to update)", slug);
+            }
+        }
+        for w in &d.warnings {
+            println!("    warning: {}", w);
+        }
+    }
+    for u in &uploads {
+        println!("  image    {} -> {}", u.src.display(), u.url);
+    }
+    println!(
+        "\n{} to create, {} to update, {} unchanged, {} skipped, {} failed, {} images to upload",
+        n_create,
+        n_update,
+        n_same,
+        n_exists,
+        failed,
+        uploads.len()
+    );
+
+    if !args.apply {
+        println!("dry run：确认无误后加 --apply 写入");
+        return Ok(());
+    }
+
+    // 3) 写入：先上传图片，再逐篇写库（每篇一个事务，单篇失败不影响其它），最后把图片登记到作者的媒体库
+    for u in &uploads {
+        let failed = importer::store_upload(&cfg, u)
+            .await
+            .with_context(|| format!("上传 {} 失败", u.src.display()))?;
+        for (file, e) in failed {
+            println!("    warning: image variant {}: {}", file, e);
+        }
+    }
+
+    let mut written = 0;
+    for d in &docs {
+        let res = match &d.action {
+            Action::Create => {
+                let author = d.author.as_ref().and_then(|n| authors.get(n)).unwrap_or(&author_id);
+                importer::create_post(&pool, &cfg, author, &d.inp, &d.ts).await
+            }
+            Action::Update { id, .. } => {
+                importer::overwrite_post(&pool, &cfg, id, &d.inp, &d.ts).await.map(|_| id.clone())
+            }
+            Action::Unchanged | Action::Exists => continue,
+        };
+        let res = match res {
+            Ok(id) => importer::register_uploads(&pool, &id, &d.image_urls, &uploads).await,
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(()) => written += 1,
+            Err(e) => println!("! error    {}  {:#}", d.file.display(), e),
+        }
+    }
+
+    println!("OK: 已写入 {} 篇文章，上传 {} 张图片", written, uploads.len());
+    Ok(())
+}
+
+fn parse_args() -> Result<Args> {
+    let mut it = env::args().skip(1);
+    let (mut dir, mut author, mut static_root) = (None, None, None);
+    let (mut overwrite, mut apply) = (false, false);
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--author" => author = it.next(),
+            "--static" => static_root = it.next().map(PathBuf::from),
+            "--overwrite" => overwrite = true,
+            "--apply" => apply = true,
+            s if s.starts_with("--") => bail!("未知参数: {}", s),
+            _ => dir = Some(PathBuf::from(a)),
+        }
+    }
+    let Some(dir) = dir else { bail!("缺少 <dir>") };
+    let Some(author) = author else { bail!("缺少 --author") };
+    Ok(Args {
+        static_root: static_root.unwrap_or_else(|| dir.clone()),
+        dir,
+        author,
+        overwrite,
+        apply,
+    })
+}
+
+fn collect_markdown(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("无法读取目录 {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_markdown(&path, out)?;
+        } else if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("md" | "markdown")
+        ) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// front matter → PostInput：
+/// title / slug / author / date / publishDate / lastmod(updated) / tags + categories /
+/// status(draft, published: false) / visibility / description(excerpt, summary) / lang / custom_fields / markdown_options
+fn load_doc(
+    file: &Path,
+    args: &Args,
+    cfg: &Config,
+    images: &mut HashMap<PathBuf, String>,
+    uploads: &mut Vec<importer::Upload>,
+) -> Result<Doc> {
+    let raw = fs::read_to_string(file)?;
+    let (meta, body) = importer::split_front_matter(&raw)?;
+    let mut warnings = vec![];
+
+    // Hugo page bundle（xxx/index.md）用目录名
+    let stem = match file.file_stem().and_then(|s| s.to_str()) {
+        Some("index" | "_index") => file
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|s| s.to_str())
+            .unwrap_or("post"),
+        Some(s) => s,
+        None => "post",
+    };
+    // Jekyll 文件名带日期前缀：2020-01-02-hello.md
+    let stem_slug = posts::slugify(stem.get(11..).filter(|_| is_date_prefixed(stem)).unwrap_or(stem));
+
+    let title = importer::meta_str(&meta, &["title"]).unwrap_or_else(|| stem.to_string());
+    let slug = importer::meta_str(&meta, &["slug"])
+        .map(|s| posts::slugify(&s))
+        .filter(|s| !s.is_empty())
+        .or_else(|| Some(stem_slug).filter(|s| !s.is_empty()))
+        .unwrap_or_else(|| Uuid::new_v4().simple().to_string()[..8].to_string());
+
+    let date = importer::meta_str(&meta, &["date"])
+        .or_else(|| is_date_prefixed(stem).then(|| stem[..10].to_string()));
+    let date = match date {
+        Some(d) => match importer::normalize_date(&d) {
+            Some(d) => Some(d),
+            None => {
+                warnings.push(format!("unrecognized date `{}`, using now", d));
+                None
+            }
+        },
+        None => None,
+    };
+    let updated = importer::meta_str(&meta, &["lastmod", "updated"])
+        .and_then(|d| importer::normalize_date(&d));
+
+    let draft = match importer::meta_str(&meta, &["status"]).as_deref() {
+        Some("draft") => true,
+        Some("published") => false,
+        Some(other) => bail!("invalid status `{}`", other),
+        None => {
+            importer::meta_bool(&meta, "draft").unwrap_or(false)
+                || importer::meta_bool(&meta, "published") == Some(false)
+        }
+    };
+    let published = importer::meta_str(&meta, &["publishDate", "published_at"])
+        .and_then(|d| importer::normalize_date(&d));
+
+    // 密码不随导出包走，protected 文章降级为 private
+    let visibility = match importer::meta_str(&meta, &["visibility"]) {
+        Some(v) if v == "protected" => {
+            warnings.push("protected post imported as private (password is not exported)".into());
+            Some("private".to_string())
+        }
+        Some(v) => {
+            posts::check_visibility(&v)?;
+            Some(v)
+        }
+        None => None,
+    };
+    let custom_fields = match meta.get("custom_fields") {
+        Some(serde_json::Value::Object(m)) => Some(m.clone()),
+        Some(_) => bail!("custom_fields must be a mapping"),
+        None => None,
+    };
+    let markdown_options = match meta.get("markdown_options") {
+        Some(serde_json::Value::Object(m)) => Some(m.clone()),
+        Some(_) => bail!("markdown_options must be a mapping"),
+        None => None,
+    };
+
+    let mut tags = importer::meta_list(&meta, "tags");
+    for c in importer::meta_list(&meta, "categories") {
+        if !tags.contains(&c) {
+            tags.push(c);
+        }
+    }
+
+    // 本地图片：和后台上传一样检查、处理后按内容哈希存放，改写链接；不合格的保留原链接
+    let md_dir = file.parent().unwrap_or(Path::new("."));
+    let mut rewrites = HashMap::new();
+    for url in importer::local_image_refs(&body) {
+        let path = importer::resolve_local(&url, md_dir, &args.static_root);
+        if !path.is_file() {
+            warnings.push(format!("image not found: {}", url));
+            continue;
+        }
+        let key = path.canonicalize().unwrap_or(path.clone());
+        let new_url = match images.get(&key) {
+            Some(u) => u.clone(),
+            None => match importer::plan_upload(&path, cfg) {
+                Ok(u) => {
+                    let url = u.url.clone();
+                    if !uploads.iter().any(|x| x.url == url) {
+                        uploads.push(u);
+                    }
+                    images.insert(key, url.clone());
+                    url
+                }
+                Err(e) => {
+                    warnings.push(format!("image rejected: {}: {:#}", url, e));
+                    continue;
+                }
+            },
+        };
+        rewrites.insert(url, new_url);
+    }
+    let body_md = importer::rewrite_images(body.trim_start_matches(['\r', '\n']), &rewrites);
+    let image_urls = rewrites.into_values().collect();
+
+    let now = blog_backend::models::now();
+    let created = date.clone().unwrap_or_else(|| now.clone());
+    let ts = posts::Timestamps {
+        updated_at: updated.or(date.clone()).unwrap_or_else(|| now.clone()),
+        published_at: (!draft).then(|| published.unwrap_or_else(|| created.clone())),
+        created_at: created,
+    };
+
+    Ok(Doc {
+        file: file.to_path_buf(),
+        inp: PostInput {
+            title,
+            slug: Some(slug),
+            body_md,
+            tags: Some(tags),
+            status: Some(if draft { "draft" } else { "published" }.into()),
+            excerpt: importer::meta_str(&meta, &["description", "excerpt", "summary"]),
+            visibility,
+            password: None,
+            custom_fields,
+            lang: importer::meta_str(&meta, &["lang", "language"]),
+            translation_of: None,
+            markdown_options,
+        },
+        ts,
+        author: importer::meta_str(&meta, &["author"]),
+        warnings,
+        image_urls,
+        action: Action::Create,
+    })
+}
+
+fn is_date_prefixed(stem: &str) -> bool {
+    stem.len() > 11
+        && stem.as_bytes()[10] == b'-'
+        && importer::normalize_date(&stem[..10]).is_some()
+}
+
+async fn plan_action(pool: &db::Db, inp: &PostInput, overwrite: bool) -> Result<Action> {
+    let slug = inp.slug.as_deref().unwrap_or_default();
+    let Some(id) = sqlx::query_scalar!(r#"SELECT id as "id!: String" FROM posts WHERE slug = ?"#, slug)
+        .fetch_optional(pool)
+        .await?
+    else {
+        return Ok(Action::Create);
+    };
+
+    let changes = importer::changes(pool, &id, inp).await?;
+    Ok(match (changes.is_empty(), overwrite) {
+        (true, _) => Action::Unchanged,
+        (false, true) => Action::Update { id, changes },
+        (false, false) => Action::Exists,
+    })
+}
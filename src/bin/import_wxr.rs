@@ -0,0 +1,480 @@
+// src/bin/import_wxr.rs
+// 导入 WordPress 导出文件（工具 → 导出 → 所有内容，得到的 .xml）
+// 作者按登录名建号（无法用密码登录，需管理员重置），附件从本地 wp-content/uploads 副本上传进媒体库
+// 按原文章 id 记录映射，重复运行只更新有变化的文章；默认只打印计划，加 --apply 才写入
+use anyhow::{bail, Context, Result};
+use blog_backend::{
+    config::Config,
+    db, importer,
+    models::{new_id, now, PostInput},
+    posts, wxr,
+};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::PathBuf,
+};
+
+const USAGE: &str = "用法: cargo run --bin import_wxr -- <export.xml> [--media <dir>] [--author <username>] [--apply]
+  --media <dir>       wp-content/uploads 的本地副本，正文引用的附件从这里上传
+  --author <username> 找不到原作者时归到该用户名下（默认按原作者登录名新建用户）
+  --apply             真正写入数据库并上传附件（默认只打印计划）";
+
+// 新建作者的占位密码哈希：不是合法的 PHC 串，任何密码都无法登录
+const NO_LOGIN_HASH: &str = "!";
+
+struct Args {
+    file: PathBuf,
+    media: Option<PathBuf>,
+    author: Option<String>,
+    apply: bool,
+}
+
+enum Action {
+    Create,
+    Update { post_id: String, changes: Vec<String> },
+    Unchanged,
+    Exists, // slug 被不是本次来源导入的文章占用
+}
+
+struct Doc {
+    wp_id: String,
+    creator: String,
+    inp: PostInput,
+    ts: posts::Timestamps,
+    warnings: Vec<String>,
+    file_urls: Vec<String>,
+    action: Action,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let args = match parse_args() {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let cfg = Config::new()?;
+    let pool = db::init_pool(&cfg.database_url).await?;
+    db::migrate(&pool).await?;
+
+    let xml = fs::read_to_string(&args.file)
+        .with_context(|| format!("无法读取 {}", args.file.display()))?;
+    let site = wxr::parse(&xml)?;
+    let source = format!("wordpress:{}", site.link.trim_end_matches('/'));
+
+    // 1) 作者：已存在的用户名直接用，其余新建
+    let mut logins: Vec<(String, Option<String>)> = site
+        .authors
+        .iter()
+        .map(|a| (a.login.clone(), a.display_name.clone()))
+        .collect();
+    for it in &site.items {
+        if !it.creator.is_empty() && !logins.iter().any(|(l, _)| *l == it.creator) {
+            logins.push((it.creator.clone(), None));
+        }
+    }
+    let mut users: HashMap<String, String> = HashMap::new(); // 登录名 → 用户 id
+    let mut new_users = vec![];
+    for (login, display) in &logins {
+        let id = sqlx::query_scalar!(
+            r#"SELECT id as "id!: String" FROM users WHERE username = ?"#,
+            login
+        )
+        .fetch_optional(&pool)
+        .await?;
+        match (id, &args.author) {
+            (Some(id), _) => {
+                users.insert(login.clone(), id);
+            }
+            (None, None) => new_users.push((login.clone(), display.clone())),
+            (None, Some(_)) => {}
+        }
+    }
+    let fallback = match &args.author {
+        Some(name) => Some(
+            sqlx::query_scalar!(r#"SELECT id as "id!: String" FROM users WHERE username = ?"#, name)
+                .fetch_optional(&pool)
+                .await?
+                .with_context(|| format!("用户 `{}` 不存在（先用 mkuser 创建）", name))?,
+        ),
+        None => None,
+    };
+
+    // 2) 文章
+    let mut docs: Vec<Doc> = vec![];
+    let mut uploads: Vec<importer::Upload> = vec![];
+    let mut files: HashMap<PathBuf, String> = HashMap::new(); // 本地附件 → /uploads/ URL
+    let mut skipped: HashMap<String, usize> = HashMap::new(); // 非文章条目按类型计数
+    let mut seen_slugs = HashSet::new();
+    let mut failed = 0;
+
+    for it in &site.items {
+        if it.post_type != "post" || matches!(it.status.as_str(), "trash" | "auto-draft" | "inherit") {
+            let kind = if it.post_type == "post" { it.status.clone() } else { it.post_type.clone() };
+            *skipped.entry(kind).or_default() += 1;
+            continue;
+        }
+        let mut doc = match load_item(it, &args, &cfg, &mut files, &mut uploads) {
+            Ok(d) => d,
+            Err(e) => {
+                println!("! error    wp#{}  {:#}", it.id, e);
+                failed += 1;
+                continue;
+            }
+        };
+        // 同一导出里 slug 重复（WordPress 草稿常见）时加上原 id
+        let slug = doc.inp.slug.clone().unwrap_or_default();
+        if !seen_slugs.insert(slug.clone()) {
+            let alt = format!("{}-{}", slug, it.id);
+            doc.warnings.push(format!("duplicate slug `{}`, using `{}`", slug, alt));
+            seen_slugs.insert(alt.clone());
+            doc.inp.slug = Some(alt);
+        }
+        doc.action = plan_action(&pool, &source, &doc).await?;
+        docs.push(doc);
+    }
+
+    let needed: HashSet<&str> = docs
+        .iter()
+        .filter(|d| matches!(d.action, Action::Create | Action::Update { .. }))
+        .flat_map(|d| d.file_urls.iter().map(String::as_str))
+        .collect();
+    uploads.retain(|u| needed.contains(u.url.as_str()));
+
+    // 3) 打印计划
+    println!("source: {}", source);
+    for (login, _) in &new_users {
+        println!("+ user     {}", login);
+    }
+    let (mut n_create, mut n_update, mut n_same, mut n_exists) = (0, 0, 0, 0);
+    for d in &docs {
+        let slug = d.inp.slug.as_deref().unwrap_or_default();
+        match &d.action {
+            Action::Create => {
+                n_create += 1;
+                println!(
+                    "+ create   {:<32} \"{}\" [wp#{}, {} tags, {}]",
+                    slug,
+                    d.inp.title,
+                    d.wp_id,
+                    d.inp.tags.as_ref().map_or(0, Vec::len),
+                    d.inp.status.as_deref().unwrap_or("draft")
+                );
+            }
+            Action::Update { changes, .. } => {
+                n_update += 1;
+                println!("~ update   {:<32} {}", slug, changes.join(", "));
+            }
+            Action::Unchanged => {
+                n_same += 1;
+                println!("= same     {}", slug);
+            }
+            Action::Exists => {
+                n_exists += 1;
+                println!("! exists   {:<32} (slug taken by a post not imported from this site)", slug);
+            }
+        }
+        for w in &d.warnings {
+            println!("    warning: {}", w);
+        }
+    }
+    for u in &uploads {
+        println!("  file     {} -> {}", u.src.display(), u.url);
+    }
+    let mut skipped: Vec<_> = skipped.into_iter().collect();
+    skipped.sort();
+    for (kind, n) in &skipped {
+        println!("  skip     {} × {}", n, kind);
+    }
+    println!(
+        "\n{} users to create, {} posts to create, {} to update, {} unchanged, {} skipped, {} failed, {} files to upload",
+        new_users.len(),
+        n_create,
+        n_update,
+        n_same,
+        n_exists,
+        failed,
+        uploads.len()
+    );
+
+    if !args.apply {
+        println!("dry run：确认无误后加 --apply 写入");
+        return Ok(());
+    }
+
+    // 4) 写入：用户 → 附件 → 文章（每篇一个事务）
+    for (login, display) in &new_users {
+        let id = new_id();
+        let created = now();
+        sqlx::query!(
+            "INSERT INTO users (id, username, password_hash, created_at, display_name) VALUES (?,?,?,?,?)",
+            id,
+            login,
+            NO_LOGIN_HASH,
+            created,
+            display
+        )
+        .execute(&pool)
+        .await?;
+        users.insert(login.clone(), id);
+    }
+
+    for u in &uploads {
+        let failed = importer::store_upload(&cfg, u)
+            .await
+            .with_context(|| format!("上传 {} 失败", u.src.display()))?;
+        for (file, e) in failed {
+            println!("    warning: image variant {}: {}", file, e);
+        }
+    }
+
+    let mut written = 0;
+    for d in &docs {
+        let res = match &d.action {
+            Action::Create => {
+                let Some(author) = users.get(&d.creator).or(fallback.as_ref()) else {
+                    println!("! error    wp#{}  unknown author `{}`", d.wp_id, d.creator);
+                    continue;
+                };
+                create_post(&pool, &cfg, &source, author, d).await
+            }
+            Action::Update { post_id, .. } => {
+                let res = importer::overwrite_post(&pool, &cfg, post_id, &d.inp, &d.ts).await;
+                res.map(|_| post_id.clone())
+            }
+            Action::Unchanged | Action::Exists => continue,
+        };
+        let res = match res {
+            Ok(id) => importer::register_uploads(&pool, &id, &d.file_urls, &uploads).await,
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(()) => written += 1,
+            Err(e) => println!("! error    wp#{}  {:#}", d.wp_id, e),
+        }
+    }
+
+    println!(
+        "OK: 新建 {} 个用户，写入 {} 篇文章，上传 {} 个附件",
+        new_users.len(),
+        written,
+        uploads.len()
+    );
+    Ok(())
+}
+
+fn parse_args() -> Result<Args> {
+    let mut it = env::args().skip(1);
+    let (mut file, mut media, mut author, mut apply) = (None, None, None, false);
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--media" => media = it.next().map(PathBuf::from),
+            "--author" => author = it.next(),
+            "--apply" => apply = true,
+            s if s.starts_with("--") => bail!("未知参数: {}", s),
+            _ => file = Some(PathBuf::from(a)),
+        }
+    }
+    let Some(file) = file else { bail!("缺少 <export.xml>") };
+    Ok(Args { file, media, author, apply })
+}
+
+// 正文里指向原站上传目录的地址（含缩略图变体 a-300x200.jpg）
+static RE_WP_UPLOAD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?:https?:)?(?://[^/\s"'<>]+)?/wp-content/uploads/([^\s"'<>()?#\]]+)"#).unwrap()
+});
+
+/// WXR 条目 → PostInput：publish → 已发布、private → 已发布 + private、有密码 → protected，其余为草稿
+fn load_item(
+    it: &wxr::Item,
+    args: &Args,
+    cfg: &Config,
+    files: &mut HashMap<PathBuf, String>,
+    uploads: &mut Vec<importer::Upload>,
+) -> Result<Doc> {
+    let mut warnings = vec![];
+
+    // 附件：本地副本存在就和后台上传一样检查、处理后按内容哈希存放并改写地址；不合格的保留原地址
+    let mut file_urls = vec![];
+    let (mut missing, mut rejected) = (vec![], vec![]);
+    let html = RE_WP_UPLOAD.replace_all(&it.content, |c: &Captures| {
+        let rel = c[1].replace("%20", " ");
+        let local = args.media.as_ref().map(|m| m.join(&rel)).filter(|p| p.is_file());
+        let Some(local) = local.filter(|_| !rel.split('/').any(|s| s == "..")) else {
+            if !missing.contains(&c[0].to_string()) {
+                missing.push(c[0].to_string());
+            }
+            return c[0].to_string();
+        };
+        let key = local.canonicalize().unwrap_or(local.clone());
+        let url = match files.get(&key) {
+            Some(u) => u.clone(),
+            None => match importer::plan_upload(&local, cfg) {
+                Ok(u) => {
+                    let url = u.url.clone();
+                    if !uploads.iter().any(|x| x.url == url) {
+                        uploads.push(u);
+                    }
+                    files.insert(key, url.clone());
+                    url
+                }
+                Err(e) => {
+                    let msg = format!("{}: {:#}", &c[0], e);
+                    if !rejected.contains(&msg) {
+                        rejected.push(msg);
+                    }
+                    return c[0].to_string();
+                }
+            },
+        };
+        if !file_urls.contains(&url) {
+            file_urls.push(url.clone());
+        }
+        url
+    });
+    for m in missing {
+        warnings.push(format!("attachment not found locally: {}", m));
+    }
+    for m in rejected {
+        warnings.push(format!("attachment rejected: {}", m));
+    }
+
+    let (body_md, shortcodes) = wxr::to_markdown(&html);
+    warnings.extend(shortcodes);
+
+    let excerpt = Some(wxr::to_markdown(&it.excerpt).0).filter(|e| !e.is_empty());
+
+    let name = percent_decode(&it.name);
+    let slug = Some(posts::slugify(&name))
+        .filter(|s| !s.is_empty())
+        .or_else(|| Some(posts::slugify(&it.title)).filter(|s| !s.is_empty()))
+        .unwrap_or_else(|| format!("wp-{}", it.id));
+
+    let (status, visibility) = match it.status.as_str() {
+        "publish" => ("published", "public"),
+        "private" => ("published", "private"),
+        "future" => {
+            warnings.push("scheduled post imported as draft".into());
+            ("draft", "public")
+        }
+        _ => ("draft", "public"),
+    };
+    let (visibility, password) = if it.password.is_empty() {
+        (visibility, None)
+    } else {
+        ("protected", Some(it.password.clone()))
+    };
+
+    let mut tags = it.categories.clone();
+    for t in &it.tags {
+        if !tags.contains(t) {
+            tags.push(t.clone());
+        }
+    }
+    tags.retain(|t| t != "Uncategorized" && t != "未分类");
+
+    let now = now();
+    let created = it.date.clone().unwrap_or_else(|| now.clone());
+    let ts = posts::Timestamps {
+        updated_at: it.modified.clone().unwrap_or_else(|| created.clone()),
+        published_at: (status == "published").then(|| created.clone()),
+        created_at: created,
+    };
+
+    Ok(Doc {
+        wp_id: it.id.clone(),
+        creator: it.creator.clone(),
+        inp: PostInput {
+            title: if it.title.is_empty() { format!("(untitled #{})", it.id) } else { it.title.clone() },
+            slug: Some(slug),
+            body_md,
+            tags: Some(tags),
+            status: Some(status.into()),
+            excerpt,
+            visibility: Some(visibility.into()),
+            password,
+            custom_fields: None,
+            lang: None,
+            translation_of: None,
+            markdown_options: None,
+        },
+        ts,
+        warnings,
+        file_urls,
+        action: Action::Create,
+    })
+}
+
+/// post_name 里的中文等会被 WordPress 百分号编码
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(b) = s.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok())
+        {
+            out.push(b);
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+async fn plan_action(pool: &db::Db, source: &str, doc: &Doc) -> Result<Action> {
+    let mapped = sqlx::query_scalar!(
+        "SELECT post_id FROM imported_items WHERE source = ? AND source_id = ?",
+        source,
+        doc.wp_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    if let Some(post_id) = mapped {
+        let changes = importer::changes(pool, &post_id, &doc.inp).await?;
+        return Ok(if changes.is_empty() {
+            Action::Unchanged
+        } else {
+            Action::Update { post_id, changes }
+        });
+    }
+
+    let slug = doc.inp.slug.as_deref().unwrap_or_default();
+    let taken = sqlx::query_scalar!("SELECT 1 FROM posts WHERE slug = ?", slug)
+        .fetch_optional(pool)
+        .await?;
+    Ok(if taken.is_some() { Action::Exists } else { Action::Create })
+}
+
+/// 新建文章并记录来源映射（同一事务），返回文章 id
+async fn create_post(
+    pool: &db::Db,
+    cfg: &Config,
+    source: &str,
+    author_id: &str,
+    d: &Doc,
+) -> Result<String> {
+    let mut tx = pool.begin().await?;
+    let (post_id, ..) = posts::insert_post(&mut tx, cfg, author_id, &d.inp, &d.ts).await?;
+    let imported_at = now();
+    sqlx::query!(
+        "INSERT INTO imported_items (source, source_id, post_id, imported_at) VALUES (?,?,?,?)",
+        source,
+        d.wp_id,
+        post_id,
+        imported_at
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(post_id)
+}
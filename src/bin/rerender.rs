@@ -0,0 +1,53 @@
+// src/bin/rerender.rs
+// 升级渲染管线（markdown::RENDERER_VERSION 变了）后重新渲染旧文章；不改 updated_at
+use anyhow::{bail, Result};
+use blog_backend::{config::Config, db, markdown::RENDERER_VERSION, rerender};
+use std::env;
+
+const USAGE: &str = "用法: cargo run --bin rerender -- [--all] [--batch <n>] [--dry-run]
+  --all        重新渲染全部文章（默认只处理 render_version 落后的）
+  --batch <n>  每批篇数（默认 100）
+  --dry-run    只统计待处理的篇数";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let (mut all, mut batch, mut dry_run) = (false, 100i64, false);
+    let mut args = env::args().skip(1);
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--all" => all = true,
+            "--dry-run" => dry_run = true,
+            "--batch" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) if n > 0 => batch = n,
+                _ => bail!("--batch 需要正整数\n{}", USAGE),
+            },
+            s => bail!("未知参数: {}\n{}", s, USAGE),
+        }
+    }
+
+    let cfg = Config::new()?;
+    let pool = db::init_pool(&cfg.database_url).await?;
+    db::migrate(&pool).await?;
+
+    if dry_run {
+        println!(
+            "renderer version {}: {} posts to re-render",
+            RENDERER_VERSION,
+            rerender::pending(&pool, all).await?
+        );
+        return Ok(());
+    }
+
+    let p = rerender::run(&pool, &cfg, all, batch, |p| {
+        println!("{}/{} rendered, {} failed", p.done, p.total, p.failed);
+    })
+    .await?;
+    for e in &p.errors {
+        println!("  error  {}", e);
+    }
+    if p.failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
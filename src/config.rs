@@ -1,21 +1,68 @@
 // ! 处理信号连接和读取配置
+use std::sync::Arc;
+
+use crate::{custom_fields, highlight, images, markdown, media, sanitize, shortcodes, storage};
+
 #[derive(Clone)]
 pub struct Config {
     pub bind: String,  //"0.0.0.0:8080"
     pub database_url: String, // "sqlite://blog.db"
     pub jwt_secret: String, // "jwt secret key"
     pub upload_dir: String, // "uploads"
+    pub storage: Arc<dyn storage::Storage>, // 上传文件的存放位置：STORAGE=local（UPLOAD_DIR）| s3
     pub site_base: String, // "http://example.com"
+    pub custom_fields: Option<custom_fields::Schema>, // CUSTOM_FIELDS_SCHEMA=./custom_fields.json（可选）
+    pub default_lang: String, // 未指定语言的文章默认语言，"zh"
+    pub admin_users: Vec<String>, // ADMIN_USERS=alice,bob：可导出 / 备份整站的用户名
+    pub sanitize: sanitize::Policy, // 渲染后 HTML 的白名单（SANITIZE_* / EMBED_HOSTS）
+    pub highlight_theme: String, // 代码高亮配色，HIGHLIGHT_THEME=InspiredGitHub
+    pub markdown: markdown::Options, // 站点默认渲染选项，MARKDOWN_OPTIONS=superscript,-footnotes
+    pub shortcodes: shortcodes::Registry, // 内置短代码 + SHORTCODE_DIR 下的模板
+    pub images: images::Settings, // 上传图片的缩小版 / 格式 / EXIF（IMAGE_*）
+    pub upload_limits: media::Limits, // 按类型的上传大小上限（UPLOAD_LIMITS / UPLOAD_MAX_REQUEST）
 }
 
 impl Config {
     pub fn new() -> anyhow::Result<Self> {
+        let custom_fields = match std::env::var("CUSTOM_FIELDS_SCHEMA") {
+            Ok(path) if !path.is_empty() => Some(custom_fields::Schema::load(&path)?),
+            _ => None,
+        };
+        let site_base = std::env::var("SITE_BASE").unwrap_or("http://localhost:8080".into());
+        let highlight_theme =
+            std::env::var("HIGHLIGHT_THEME").unwrap_or(highlight::DEFAULT_THEME.into());
+        if highlight::css(&highlight_theme).is_none() {
+            anyhow::bail!(
+                "HIGHLIGHT_THEME: unknown theme `{}` (available: {})",
+                highlight_theme,
+                highlight::themes().join(", ")
+            );
+        }
+        let upload_dir = std::env::var("UPLOAD_DIR").unwrap_or("./uploads".into());
         Ok(Self {
                 bind: std::env::var("BIND").unwrap_or("0.0.0.0:8080".into()),
                 database_url: std::env::var("DATABASE_URL")?,
                 jwt_secret: std::env::var("JWT_SECRET")?,
-                upload_dir: std::env::var("UPLOAD_DIR").unwrap_or("./uploads".into()),
-                site_base: std::env::var("SITE_BASE").unwrap_or("http://localhost:8080".into()),
+                storage: storage::from_env(&upload_dir)?,
+                upload_dir,
+                sanitize: sanitize::Policy::from_env(&site_base)?,
+                site_base,
+                highlight_theme,
+                markdown: markdown::Options::parse_list(
+                    &std::env::var("MARKDOWN_OPTIONS").unwrap_or_default(),
+                )
+                .map_err(|e| anyhow::anyhow!("MARKDOWN_OPTIONS: {}", e))?,
+                shortcodes: shortcodes::Registry::from_env()?,
+                images: images::Settings::from_env()?,
+                upload_limits: media::Limits::from_env()?,
+                custom_fields,
+                default_lang: std::env::var("DEFAULT_LANG").unwrap_or("zh".into()),
+                admin_users: std::env::var("ADMIN_USERS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
         })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,143 @@
+// 文章自定义字段（类似 front matter 的扩展数据）
+// posts.custom_fields 存 JSON 对象；如配置了 CUSTOM_FIELDS_SCHEMA，则按 schema 校验
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::error::AppError;
+
+pub type Fields = Map<String, Value>;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    #[default]
+    String,
+    Number,
+    Boolean,
+    Date, // YYYY-MM-DD 或 RFC3339
+    Url,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldDef {
+    #[serde(rename = "type", default)]
+    pub kind: FieldKind,
+    #[serde(default)]
+    pub required: bool,
+    /// 可选：枚举值（仅 string 类型有意义）
+    pub options: Option<Vec<String>>,
+}
+
+/// schema 文件示例：
+/// { "allow_unknown": false,
+///   "fields": { "difficulty": { "type": "string", "options": ["easy","hard"] },
+///               "event_date": { "type": "date", "required": true } } }
+#[derive(Debug, Clone, Deserialize)]
+pub struct Schema {
+    #[serde(default)]
+    pub allow_unknown: bool,
+    #[serde(default)]
+    pub fields: HashMap<String, FieldDef>,
+}
+
+impl Schema {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let raw = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&raw)?)
+    }
+}
+
+/// 字段名只允许字母数字下划线（同时保证拼接 JSON path 安全）
+pub fn valid_key(k: &str) -> bool {
+    !k.is_empty() && k.len() <= 64 && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// json_extract 用的路径：$."key"
+pub fn json_path(k: &str) -> String {
+    format!("$.\"{}\"", k)
+}
+
+pub fn validate(fields: &Fields, schema: Option<&Schema>) -> Result<(), AppError> {
+    for k in fields.keys() {
+        if !valid_key(k) {
+            return Err(AppError::BadRequest(format!("invalid custom field name: {}", k)));
+        }
+    }
+
+    let Some(schema) = schema else { return Ok(()) };
+
+    for (name, def) in &schema.fields {
+        match fields.get(name) {
+            None | Some(Value::Null) => {
+                if def.required {
+                    return Err(AppError::BadRequest(format!("custom field `{}` is required", name)));
+                }
+            }
+            Some(v) => check_value(name, def, v)?,
+        }
+    }
+
+    if !schema.allow_unknown
+        && let Some(k) = fields.keys().find(|k| !schema.fields.contains_key(*k))
+    {
+        return Err(AppError::BadRequest(format!("unknown custom field: {}", k)));
+    }
+    Ok(())
+}
+
+fn check_value(name: &str, def: &FieldDef, v: &Value) -> Result<(), AppError> {
+    let bad = |want: &str| AppError::BadRequest(format!("custom field `{}` must be {}", name, want));
+    match def.kind {
+        FieldKind::String => {
+            let s = v.as_str().ok_or_else(|| bad("a string"))?;
+            if let Some(opts) = &def.options
+                && !opts.iter().any(|o| o == s)
+            {
+                return Err(bad(&format!("one of: {}", opts.join(", "))));
+            }
+        }
+        FieldKind::Number => {
+            if !v.is_number() {
+                return Err(bad("a number"));
+            }
+        }
+        FieldKind::Boolean => {
+            if !v.is_boolean() {
+                return Err(bad("a boolean"));
+            }
+        }
+        FieldKind::Date => {
+            let s = v.as_str().ok_or_else(|| bad("a date string"))?;
+            let ok = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok()
+                || chrono::DateTime::parse_from_rfc3339(s).is_ok();
+            if !ok {
+                return Err(bad("a date (YYYY-MM-DD or RFC3339)"));
+            }
+        }
+        FieldKind::Url => {
+            let s = v.as_str().ok_or_else(|| bad("a URL string"))?;
+            if !(s.starts_with("http://") || s.starts_with("https://")) {
+                return Err(bad("an http(s) URL"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 数据库中的 JSON 文本 → Value（损坏时退化为空对象）
+pub fn parse(raw: &str) -> Value {
+    serde_json::from_str::<Value>(raw)
+        .ok()
+        .filter(Value::is_object)
+        .unwrap_or_else(|| Value::Object(Map::new()))
+}
+
+/// 列表过滤用：把查询串里的值转成 JSON 字面量（数字/布尔按类型比较，其余按字符串）
+pub fn filter_literal(v: &str) -> String {
+    match serde_json::from_str::<Value>(v) {
+        Ok(j @ (Value::Number(_) | Value::Bool(_))) => j.to_string(),
+        _ => Value::String(v.to_string()).to_string(),
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -21,6 +21,14 @@ pub enum AppError {
     #[error("bad request: {0}")]
     BadRequest(String),
 
+    // 与现有数据冲突（比如删除仍被文章引用的媒体）
+    #[error("conflict: {0}")]
+    Conflict(String),
+
+    // 尝试过于频繁，需等待的秒数
+    #[error("too many attempts, retry in {0}s")]
+    TooManyRequests(u64),
+
     #[error(transparent)]
     Sqlx(#[from] sqlx::Error),
 
@@ -40,8 +48,15 @@ impl IntoResponse for AppError {
             AppError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
             AppError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()), // ✅ 403
             AppError::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            // 内部错误不把具体信息暴露给客户端
+            AppError::Conflict(_) => (StatusCode::CONFLICT, self.to_string()),
+            AppError::TooManyRequests(secs) => {
+                let headers = [(header::RETRY_AFTER, secs.to_string())];
+                return (StatusCode::TOO_MANY_REQUESTS, headers, Json(ErrBody { error: self.to_string() }))
+                    .into_response();
+            }
+            // 内部错误不把具体信息暴露给客户端，只写日志（数据库、对象存储等故障要能查到原因）
             AppError::Sqlx(_) | AppError::Anyhow(_) => {
+                tracing::error!("{:#}", self);
                 (StatusCode::INTERNAL_SERVER_ERROR, "internal error".into())
             }
         };
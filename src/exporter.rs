@@ -0,0 +1,181 @@
+// 整站导出：每篇文章一个 posts/<slug>.md（YAML front matter）+ 正文引用到的上传文件 uploads/...
+// 目录结构正好是 import 命令的输入：解包后 `import <dir> --author <user>` 即可原样导回
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::Path,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    custom_fields,
+    db::Db,
+    storage::{self, Storage},
+};
+
+/// 导出内容：文章在内存里；上传文件只记路径，写出时才逐个从存储读取（一次只有一个文件在内存里）
+pub struct Export {
+    pub posts: Vec<(String, String)>, // 归档内相对路径 → Markdown
+    pub uploads: Vec<String>,         // 正文引用到的上传文件（相对 /uploads/）
+}
+
+/// front matter 字段顺序即输出顺序；键名与 import 识别的写法一致
+#[derive(Serialize)]
+struct FrontMatter {
+    title: String,
+    slug: String,
+    author: String,
+    date: String,
+    #[serde(rename = "publishDate", skip_serializing_if = "Option::is_none")]
+    publish_date: Option<String>,
+    updated: String,
+    status: String,
+    visibility: String,
+    lang: String,
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    excerpt: Option<String>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    custom_fields: Map<String, Value>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    markdown_options: Map<String, Value>,
+}
+
+// 正文里的站内上传链接：![](/uploads/a.png)、src="/uploads/a.png"、[x]: /uploads/a.pdf …
+static RE_UPLOAD: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?:^|[\s("'<=])/uploads/([^\s)"'<>?#]+)"#).unwrap());
+
+/// author_id 为 None 时导出全部作者的文章
+pub async fn collect(db: &Db, author_id: Option<&str>) -> anyhow::Result<Export> {
+    let rows = sqlx::query!(
+        r#"SELECT p.id as "id!: String", p.slug, p.title, p.excerpt, p.body_md, p.status,
+                  p.visibility as "visibility!: String", p.lang as "lang!: String", p.custom_fields,
+                  p.markdown_options, p.created_at, p.updated_at, p.published_at, u.username
+             FROM posts p JOIN users u ON u.id = p.author_id
+            WHERE (? IS NULL OR p.author_id = ?)
+            ORDER BY p.created_at"#,
+        author_id,
+        author_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for r in sqlx::query!(
+        r#"SELECT pt.post_id as "post_id!: String", t.name
+             FROM post_tags pt JOIN tags t ON t.id = pt.tag_id ORDER BY t.name"#
+    )
+    .fetch_all(db)
+    .await?
+    {
+        tags.entry(r.post_id).or_default().push(r.name);
+    }
+
+    let mut export = Export { posts: vec![], uploads: vec![] };
+
+    for r in rows {
+        let fm = FrontMatter {
+            title: r.title,
+            slug: r.slug.clone(),
+            author: r.username,
+            date: r.created_at,
+            publish_date: r.published_at,
+            updated: r.updated_at,
+            status: r.status,
+            visibility: r.visibility,
+            lang: r.lang,
+            tags: tags.remove(&r.id).unwrap_or_default(),
+            excerpt: r.excerpt.filter(|e| !e.is_empty()),
+            custom_fields: match custom_fields::parse(&r.custom_fields) {
+                Value::Object(m) => m,
+                _ => Map::new(),
+            },
+            markdown_options: r
+                .markdown_options
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+        };
+        let yaml = serde_yaml::to_string(&fm)?;
+        let md = format!("---\n{}---\n\n{}\n", yaml, r.body_md.trim_end());
+
+        for c in RE_UPLOAD.captures_iter(&r.body_md) {
+            let rel = c[1].replace("%20", " ");
+            if !export.uploads.contains(&rel) {
+                export.uploads.push(rel);
+            }
+        }
+
+        let name = r.slug.replace(['/', '\\'], "-");
+        export.posts.push((format!("posts/{}.md", name), md));
+    }
+    Ok(export)
+}
+
+// 只认普通相对路径，防止 ../ 逃逸；不合法或不存在都算找不到
+async fn fetch(storage: &dyn Storage, rel: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    Ok(match storage::key(rel) {
+        Some(key) => storage.get(key).await?,
+        None => None,
+    })
+}
+
+/// 打成 .tar.gz 写进 out（顶层目录为 prefix）；返回正文引用了但存储里找不到的文件
+/// 要同步等待存储读取，须在 spawn_blocking 的线程里调用
+pub fn write_tar_gz(
+    export: &Export,
+    storage: &dyn Storage,
+    prefix: &str,
+    out: impl Write,
+) -> anyhow::Result<Vec<String>> {
+    let handle = tokio::runtime::Handle::current();
+    let mut tar = tar::Builder::new(GzEncoder::new(out, Compression::default()));
+    let mtime = chrono::Utc::now().timestamp() as u64;
+    let mut append = |path: &str, data: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        tar.append_data(&mut header, format!("{}/{}", prefix, path), data)
+    };
+    for (path, md) in &export.posts {
+        append(path, md.as_bytes())?;
+    }
+    let mut missing = vec![];
+    for rel in &export.uploads {
+        match handle.block_on(fetch(storage, rel))? {
+            Some(data) => append(&format!("uploads/{}", rel), &data)?,
+            None => missing.push(format!("/uploads/{}", rel)),
+        }
+    }
+    tar.into_inner()?.finish()?.flush()?;
+    Ok(missing)
+}
+
+/// 直接写成目录（命令行导出到本地时用）；返回找不到的上传文件
+pub async fn write_dir(export: &Export, storage: &dyn Storage, dir: &Path) -> anyhow::Result<Vec<String>> {
+    let write = |path: &str, data: &[u8]| -> anyhow::Result<()> {
+        let dest = dir.join(path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&dest, data)?;
+        Ok(())
+    };
+    for (path, md) in &export.posts {
+        write(path, md.as_bytes())?;
+    }
+    let mut missing = vec![];
+    for rel in &export.uploads {
+        match fetch(storage, rel).await? {
+            Some(data) => write(&format!("uploads/{}", rel), &data)?,
+            None => missing.push(format!("/uploads/{}", rel)),
+        }
+    }
+    Ok(missing)
+}
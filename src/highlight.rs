@@ -0,0 +1,171 @@
+// 代码块服务端高亮：按语言切分 token，输出带 class 的 <span>（配色全在 CSS 里，换主题不用重新渲染）
+// 每行包成 <span class="line">，行号由 CSS 计数器生成；```rust {3,5-7} 标出的行加 hl
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    sync::Mutex,
+};
+
+use comrak::adapters::SyntaxHighlighterAdapter;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use syntect::{
+    highlighting::ThemeSet,
+    html::{css_for_theme_with_class_style, line_tokens_to_classed_spans, ClassStyle},
+    parsing::{ParseState, ScopeStack, ScopeStackOp, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+// token class 统一加前缀，避免和页面自己的样式冲突
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+// 一个代码块最多标出的行数
+const MAX_MARKED: usize = 10_000;
+
+static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+static THEMES: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+static RE_LINES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{([\d\s,-]*)\}").unwrap());
+
+// 行布局、行号与高亮行；主题只负责 token 配色
+const LINE_CSS: &str = "pre.highlight code { counter-reset: line; }
+pre.highlight .line { display: flex; }
+pre.highlight .line::before { counter-increment: line; content: counter(line); flex: none; width: 2.5em; padding-right: 1em; text-align: right; opacity: .5; user-select: none; }
+pre.highlight .line > .cl { flex: auto; }
+pre.highlight .line.hl { background: rgba(255, 221, 0, .2); }
+";
+
+/// 内置主题名
+pub fn themes() -> Vec<&'static str> {
+    THEMES.themes.keys().map(String::as_str).collect()
+}
+
+/// 主题对应的样式表；主题不存在时为 None
+pub fn css(theme: &str) -> Option<String> {
+    let t = THEMES.themes.get(theme)?;
+    let tokens = css_for_theme_with_class_style(t, CLASS_STYLE).ok()?;
+    let bg = t.settings.background.map_or(String::new(), |c| {
+        format!("pre.highlight {{ background-color: #{:02x}{:02x}{:02x}; }}\n", c.r, c.g, c.b)
+    });
+    Some(format!("{}{}{}", LINE_CSS, bg, tokens))
+}
+
+/// comrak 代码块适配器
+/// comrak 先调 write_code_tag（带 data-meta，即语言后面的信息串）再调 write_highlighted，
+/// 中间用 meta 暂存高亮行
+#[derive(Default)]
+pub struct Highlighter {
+    meta: Mutex<String>,
+}
+
+impl SyntaxHighlighterAdapter for Highlighter {
+    fn write_highlighted(
+        &self,
+        output: &mut dyn Write,
+        lang: Option<&str>,
+        code: &str,
+    ) -> io::Result<()> {
+        let meta = std::mem::take(&mut *self.meta.lock().unwrap());
+        let marked = marked_lines(&meta, LinesWithEndings::from(code).count());
+        let syntax = lang
+            .filter(|l| !l.is_empty())
+            .and_then(|l| SYNTAXES.find_syntax_by_token(l))
+            .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text());
+
+        let mut state = ParseState::new(syntax);
+        let mut stack = ScopeStack::new();
+        for (n, line) in LinesWithEndings::from(code).enumerate() {
+            let ops = state.parse_line(line, &SYNTAXES).map_err(io::Error::other)?;
+            // 跨行的 scope（多行注释、字符串）在每行开头重新打开、行尾关闭，保证每行标签配对
+            let reopen: Vec<_> = stack.as_slice().iter().map(|s| (0, ScopeStackOp::Push(*s))).collect();
+            let (open, _) = line_tokens_to_classed_spans("", &reopen, CLASS_STYLE, &mut ScopeStack::new())
+                .map_err(io::Error::other)?;
+            let (body, _) = line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut stack)
+                .map_err(io::Error::other)?;
+            let class = if marked.contains(&(n + 1)) { "line hl" } else { "line" };
+            write!(
+                output,
+                "<span class=\"{}\"><span class=\"cl\">{}{}{}</span></span>",
+                class,
+                open,
+                body,
+                "</span>".repeat(stack.len())
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_pre_tag(
+        &self,
+        output: &mut dyn Write,
+        _attributes: HashMap<String, String>,
+    ) -> io::Result<()> {
+        output.write_all(b"<pre class=\"highlight\">")
+    }
+
+    fn write_code_tag(
+        &self,
+        output: &mut dyn Write,
+        attributes: HashMap<String, String>,
+    ) -> io::Result<()> {
+        *self.meta.lock().unwrap() = attributes.get("data-meta").cloned().unwrap_or_default();
+        match attributes.get("class") {
+            Some(class) => write!(output, "<code class=\"{}\">", escape_attr(class)),
+            None => output.write_all(b"<code>"),
+        }
+    }
+}
+
+/// "{3,5-7}" → {3, 5, 6, 7}；写错的片段和超出代码块行数的行号忽略
+/// 各区间长度累计（重复的也算）最多 MAX_MARKED 行，防止 {1-99999999,1-99999999,...} 这类写法耗尽内存和时间
+fn marked_lines(meta: &str, line_count: usize) -> HashSet<usize> {
+    let mut lines = HashSet::new();
+    let Some(c) = RE_LINES.captures(meta) else { return lines };
+    let mut budget = MAX_MARKED.min(line_count);
+    for part in c[1].split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (a, b) = part.split_once('-').unwrap_or((part, part));
+        if let (Ok(a), Ok(b)) = (a.trim().parse::<usize>(), b.trim().parse::<usize>()) {
+            if a == 0 || a > line_count || b < a {
+                continue;
+            }
+            let b = b.min(line_count).min(a.saturating_add(budget - 1));
+            lines.extend(a..=b);
+            budget -= b - a + 1;
+            if budget == 0 {
+                break;
+            }
+        }
+    }
+    lines
+}
+
+fn escape_attr(s: &str) -> String {
+    s.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(set: HashSet<usize>) -> Vec<usize> {
+        let mut v: Vec<_> = set.into_iter().collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn marked_lines_parses_ranges() {
+        assert_eq!(sorted(marked_lines("{3,5-7}", 10)), vec![3, 5, 6, 7]);
+        assert_eq!(sorted(marked_lines("{0,2-1,-3,9-20}", 10)), vec![9, 10]);
+        assert!(marked_lines("no braces", 10).is_empty());
+    }
+
+    #[test]
+    fn marked_lines_is_bounded() {
+        let max = usize::MAX.to_string();
+        assert_eq!(sorted(marked_lines(&format!("{{{}-{}}}", max, max), 5)), Vec::<usize>::new());
+        assert_eq!(sorted(marked_lines(&format!("{{4-{}}}", max), 5)), vec![4, 5]);
+        let many = format!("{{{}}}", vec!["1-99999999"; 10_000].join(","));
+        assert_eq!(marked_lines(&many, 100_000).len(), MAX_MARKED);
+    }
+}
@@ -0,0 +1,682 @@
+// 站内图片处理：
+// - 上传时按 EXIF 方向把像素摆正，默认去掉 EXIF（含 GPS 位置）
+// - 生成几档缩小版和 WebP / AVIF，和原图放在同一个存储里：<hash>.jpg → <hash>.480w.jpg、<hash>.480w.webp、<hash>.avif…
+// - 渲染时读出原图宽高、列出同格式的缩小版，输出懒加载、带 srcset 的 <img>；访问时按 Accept 换成现代格式
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Cursor},
+};
+
+use image::{
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder},
+    imageops::FilterType,
+    metadata::Orientation,
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader,
+};
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::storage::{self, Storage};
+
+const DEFAULT_WIDTHS: &str = "480,960,1600";
+const DEFAULT_FORMATS: &str = "avif,webp";
+
+// 有损编码质量（0–100）与 AVIF 编码速度（1 最慢最好，10 最快）
+const QUALITY: u8 = 82;
+const AVIF_SPEED: u8 = 8;
+
+// 预览只读文件开头取宽高；上传时已去掉 EXIF，常见图片的头都在这个范围内
+const HEAD_BYTES: usize = 64 * 1024;
+
+// 派生文件本身不再派生
+static RE_VARIANT: Lazy<Regex> = Lazy::new(|| Regex::new(r"\.\d+w\.[A-Za-z0-9]+$").unwrap());
+
+/// 额外生成的现代格式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Avif,
+    WebP,
+}
+
+impl Format {
+    pub fn ext(self) -> &'static str {
+        match self {
+            Format::Avif => "avif",
+            Format::WebP => "webp",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Format::Avif => "image/avif",
+            Format::WebP => "image/webp",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub widths: Vec<u32>,     // 缩小版宽度，从小到大
+    pub formats: Vec<Format>, // 按偏好排序：协商时排在前面的优先
+    pub keep_exif: bool,
+}
+
+impl Settings {
+    /// IMAGE_WIDTHS=480,960,1600
+    /// IMAGE_FORMATS=avif,webp（空串表示只保留原格式）
+    /// IMAGE_KEEP_EXIF=true 保留 EXIF（方向需要矫正的图仍会重新编码，EXIF 随之丢弃）
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut widths = std::env::var("IMAGE_WIDTHS")
+            .unwrap_or(DEFAULT_WIDTHS.into())
+            .split(',')
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(|w| match w.parse::<u32>() {
+                Ok(n) if (16..=8192).contains(&n) => Ok(n),
+                _ => Err(anyhow::anyhow!("IMAGE_WIDTHS: `{}` is not a width between 16 and 8192", w)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        widths.sort_unstable();
+        widths.dedup();
+        let formats = std::env::var("IMAGE_FORMATS")
+            .unwrap_or(DEFAULT_FORMATS.into())
+            .split(',')
+            .map(|f| f.trim().to_ascii_lowercase())
+            .filter(|f| !f.is_empty())
+            .map(|f| match f.as_str() {
+                "avif" => Ok(Format::Avif),
+                "webp" => Ok(Format::WebP),
+                _ => Err(anyhow::anyhow!("IMAGE_FORMATS: unknown format `{}` (avif, webp)", f)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let keep_exif = std::env::var("IMAGE_KEEP_EXIF").is_ok_and(|v| v == "1" || v == "true");
+        Ok(Self { widths, formats, keep_exif })
+    }
+}
+
+/// 一张站内图片
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<(String, u32)>, // 同格式缩小版 (URL, 宽度)，从窄到宽
+}
+
+/// 上传的原图：按方向摆正、（默认）去掉 EXIF / XMP / IPTC 等元数据。需要改写时返回新内容，可原样保存时返回 None
+/// 只处理 JPEG / PNG / WebP；GIF 可能是动图，原样保存
+pub fn prepare(data: &[u8], keep_exif: bool) -> Result<Option<Vec<u8>>, String> {
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format().map_err(|e| e.to_string())?;
+    let Some(format) = reader.format().filter(|f| processable(*f)) else { return Ok(None) };
+    let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    if orientation == Orientation::NoTransforms {
+        // 不用转方向：无损地删掉元数据段，不重新压缩（GPS 也可能只在 XMP 里）
+        if keep_exif {
+            return Ok(None);
+        }
+        let stripped = strip_metadata(data, format)?;
+        return Ok((stripped.len() != data.len()).then_some(stripped));
+    }
+    // 重新编码只写像素，EXIF 等元数据都不会带过去
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    img.apply_orientation(orientation);
+    encode(&img, format).map(Some)
+}
+
+/// 去掉元数据段，像素数据原样保留
+fn strip_metadata(data: &[u8], format: ImageFormat) -> Result<Vec<u8>, String> {
+    match format {
+        ImageFormat::Jpeg => strip_jpeg(data),
+        ImageFormat::Png => strip_png(data),
+        ImageFormat::WebP => strip_webp(data),
+        _ => Ok(data.to_vec()),
+    }
+}
+
+/// JPEG：保留 APP0（JFIF）、APP2 里的 ICC 配置和 APP14（Adobe，影响颜色转换），
+/// 其余 APPn（EXIF、XMP、IPTC、MPF……）和注释都去掉；EOI 之后附带的内容（MPF 里的其它图）也丢掉
+fn strip_jpeg(data: &[u8]) -> Result<Vec<u8>, String> {
+    let bad = || "malformed JPEG".to_string();
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(bad());
+    }
+    let mut out = data[..2].to_vec();
+    let mut i = 2;
+    loop {
+        // 标记前可以有填充的 0xFF
+        while data.get(i) == Some(&0xFF) && data.get(i + 1) == Some(&0xFF) {
+            i += 1;
+        }
+        if data.get(i) != Some(&0xFF) {
+            return Err(bad());
+        }
+        let marker = *data.get(i + 1).ok_or_else(bad)?;
+        if marker == 0xD9 {
+            out.extend_from_slice(&[0xFF, 0xD9]);
+            return Ok(out);
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            out.extend_from_slice(&data[i..i + 2]);
+            i += 2;
+            continue;
+        }
+        let len = match data.get(i + 2..i + 4) {
+            Some(b) => u16::from_be_bytes([b[0], b[1]]) as usize,
+            None => return Err(bad()),
+        };
+        let end = i + 2 + len;
+        if len < 2 || end > data.len() {
+            return Err(bad());
+        }
+        let body = &data[i + 4..end];
+        let keep = match marker {
+            0xE0 | 0xEE => true,
+            0xE2 => body.starts_with(b"ICC_PROFILE\0"),
+            0xE1 | 0xE3..=0xED | 0xEF | 0xFE => false,
+            _ => true,
+        };
+        if keep {
+            out.extend_from_slice(&data[i..end]);
+        }
+        i = end;
+        if marker == 0xDA {
+            // 扫描数据一直到下一个标记（0xFF00 是转义，0xFFD0..D7 是复位标记）
+            let start = i;
+            let is_marker = |b: u8| b != 0 && !(0xD0..=0xD7).contains(&b);
+            while i + 1 < data.len() && !(data[i] == 0xFF && is_marker(data[i + 1])) {
+                i += 1;
+            }
+            if i + 1 >= data.len() {
+                return Err(bad());
+            }
+            out.extend_from_slice(&data[start..i]);
+        }
+    }
+}
+
+/// PNG：去掉文本块（tEXt / zTXt / iTXt）、eXIf 和 tIME，IEND 之后的内容丢掉
+fn strip_png(data: &[u8]) -> Result<Vec<u8>, String> {
+    let bad = || "malformed PNG".to_string();
+    if data.len() < 8 {
+        return Err(bad());
+    }
+    let mut out = data[..8].to_vec();
+    let mut i = 8;
+    while i + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[i..i + 4].try_into().unwrap()) as usize;
+        let kind = &data[i + 4..i + 8];
+        let end = i.checked_add(12 + len).filter(|e| *e <= data.len()).ok_or_else(bad)?;
+        if !matches!(kind, b"tEXt" | b"zTXt" | b"iTXt" | b"eXIf" | b"tIME") {
+            out.extend_from_slice(&data[i..end]);
+        }
+        if kind == b"IEND" {
+            return Ok(out);
+        }
+        i = end;
+    }
+    Err(bad())
+}
+
+/// WebP：去掉 EXIF / XMP 块并清掉 VP8X 里对应的标志位，重算 RIFF 长度
+fn strip_webp(data: &[u8]) -> Result<Vec<u8>, String> {
+    let bad = || "malformed WebP".to_string();
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return Err(bad());
+    }
+    let mut out = data[..12].to_vec();
+    let mut i = 12;
+    while i + 8 <= data.len() {
+        let len = u32::from_le_bytes(data[i + 4..i + 8].try_into().unwrap()) as usize;
+        let end = i.checked_add(8 + len + len % 2).ok_or_else(bad)?.min(data.len());
+        if i + 8 + len > data.len() {
+            return Err(bad());
+        }
+        match &data[i..i + 4] {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" if len >= 1 => {
+                let at = out.len() + 8;
+                out.extend_from_slice(&data[i..end]);
+                out[at] &= !(0x08 | 0x04);
+            }
+            _ => out.extend_from_slice(&data[i..end]),
+        }
+        i = end;
+    }
+    let size = u32::try_from(out.len() - 8).map_err(|_| bad())?;
+    out[4..8].copy_from_slice(&size.to_le_bytes());
+    Ok(out)
+}
+
+fn processable(format: ImageFormat) -> bool {
+    matches!(format, ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)
+}
+
+fn encode(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    match format {
+        ImageFormat::Jpeg => {
+            let rgb = DynamicImage::ImageRgb8(img.to_rgb8());
+            rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut out, QUALITY))
+                .map_err(|e| e.to_string())?;
+        }
+        ImageFormat::WebP => {
+            let rgba = DynamicImage::ImageRgba8(img.to_rgba8());
+            let encoded = webp::Encoder::from_image(&rgba)?.encode(QUALITY as f32);
+            out.extend_from_slice(&encoded);
+        }
+        ImageFormat::Avif => {
+            let rgba = DynamicImage::ImageRgba8(img.to_rgba8());
+            rgba.write_with_encoder(AvifEncoder::new_with_speed_quality(&mut out, AVIF_SPEED, QUALITY))
+                .map_err(|e| e.to_string())?;
+        }
+        _ => img.write_to(&mut Cursor::new(&mut out), format).map_err(|e| e.to_string())?,
+    }
+    Ok(out)
+}
+
+/// 一个派生文件：名字（存储 key 或 URL）、宽度（None 为原尺寸）与格式
+#[derive(Clone, Debug)]
+pub struct Variant {
+    pub name: String,
+    pub width: Option<u32>,
+    format: ImageFormat,
+}
+
+impl Variant {
+    pub fn mime(&self) -> &'static str {
+        self.format.to_mime_type()
+    }
+}
+
+/// 全部派生文件：比原图窄的各档（原格式 + 额外格式）与原尺寸的额外格式
+fn plan(settings: &Settings, name: &str, width: u32, format: ImageFormat) -> Vec<Variant> {
+    let ext = extension_of(name);
+    let extra: Vec<Format> =
+        settings.formats.iter().copied().filter(|f| image_format(*f) != format).collect();
+    let mut jobs: Vec<(Option<u32>, ImageFormat, &str)> = vec![];
+    for &w in settings.widths.iter().filter(|&&w| w < width) {
+        jobs.push((Some(w), format, ext));
+        for f in &extra {
+            jobs.push((Some(w), image_format(*f), f.ext()));
+        }
+    }
+    for f in &extra {
+        jobs.push((None, image_format(*f), f.ext()));
+    }
+    jobs.into_iter()
+        .filter_map(|(width, format, ext)| {
+            Some(Variant { name: variant_name(name, width, ext)?, width, format })
+        })
+        .collect()
+}
+
+fn image_format(f: Format) -> ImageFormat {
+    match f {
+        Format::Avif => ImageFormat::Avif,
+        Format::WebP => ImageFormat::WebP,
+    }
+}
+
+/// 格式与宽高（只读文件头）
+fn header(data: &[u8]) -> Option<(ImageFormat, u32, u32)> {
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format().ok()?;
+    let format = reader.format()?;
+    let (width, height) = reader.into_dimensions().ok()?;
+    Some((format, width, height))
+}
+
+/// 一张上传图片应有的派生文件；不处理的格式、本身就是派生文件的为空
+pub fn variants(settings: &Settings, name: &str, data: &[u8]) -> Vec<Variant> {
+    match header(data) {
+        Some((format, width, _)) if processable(format) && !RE_VARIANT.is_match(name) => {
+            plan(settings, name, width, format)
+        }
+        _ => vec![],
+    }
+}
+
+/// 编码派生文件（原图只解码一次），结果与 variants 一一对应；CPU 密集，放在阻塞线程里调用
+pub fn derive(data: &[u8], variants: &[Variant]) -> Vec<Result<Vec<u8>, String>> {
+    let img = match image::load_from_memory(data) {
+        Ok(img) => img,
+        Err(e) => return variants.iter().map(|_| Err(e.to_string())).collect(),
+    };
+    variants
+        .iter()
+        .map(|v| match v.width {
+            Some(w) => {
+                let h = ((img.height() as u64 * w as u64) / img.width() as u64).max(1) as u32;
+                encode(&img.resize_exact(w, h, FilterType::Lanczos3), v.format)
+            }
+            None => encode(&img, v.format),
+        })
+        .collect()
+}
+
+/// 编码并写入还不存在的派生文件；返回失败的文件及原因
+async fn store_variants(
+    storage: &dyn Storage,
+    data: Vec<u8>,
+    wanted: Vec<Variant>,
+) -> Vec<(String, String)> {
+    let mut failed = vec![];
+    let mut missing = vec![];
+    for v in wanted {
+        match storage.exists(&v.name).await {
+            Ok(false) => missing.push(v),
+            Ok(true) => {}
+            Err(e) => failed.push((v.name, e.to_string())),
+        }
+    }
+    if missing.is_empty() {
+        return failed;
+    }
+    let jobs = missing.clone();
+    let encoded = tokio::task::spawn_blocking(move || derive(&data, &jobs))
+        .await
+        .unwrap_or_else(|e| vec![Err(e.to_string())]);
+    for (v, out) in missing.into_iter().zip(encoded) {
+        let written = match out {
+            Ok(bytes) => storage.put(&v.name, &bytes, v.mime()).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            failed.push((v.name, e));
+        }
+    }
+    failed
+}
+
+/// 上传后生成全部派生文件（已存在的跳过）；返回生成失败的文件及原因
+pub async fn generate(
+    settings: &Settings,
+    storage: &dyn Storage,
+    key: &str,
+    data: Vec<u8>,
+) -> Vec<(String, String)> {
+    let wanted = variants(settings, key, &data);
+    store_variants(storage, data, wanted).await
+}
+
+/// 派生文件的地址（上传接口返回给前端，可直接引用）：[(URL, 宽度, MIME)]
+pub fn planned(settings: &Settings, url: &str, width: u32, mime: &str) -> Vec<(String, u32, String)> {
+    let Some(format) = ImageFormat::from_mime_type(mime).filter(|f| processable(*f)) else {
+        return vec![];
+    };
+    plan(settings, url, width, format)
+        .into_iter()
+        .map(|v| {
+            let mime = v.mime().to_string();
+            (v.name, v.width.unwrap_or(width), mime)
+        })
+        .collect()
+}
+
+/// 逐个读取 /uploads/... 图片；文件不存在或不是图片的不在结果里
+/// 同格式的缩小版缺了就补上（早于图片处理上传、或从导出包导入的图）
+pub async fn resolve(
+    settings: &Settings,
+    storage: &dyn Storage,
+    urls: &[String],
+) -> HashMap<String, Image> {
+    let mut found = HashMap::new();
+    for url in urls {
+        if url.contains(['?', '#']) {
+            continue;
+        }
+        let Some(key) = url.strip_prefix("/uploads/").and_then(storage::key) else { continue };
+        let data = match storage.get(key).await {
+            Ok(Some(data)) => data,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!("image {}: {}", url, e);
+                continue;
+            }
+        };
+        if let Some(img) = inspect(settings, storage, key, url, data).await {
+            found.insert(url.clone(), img);
+        }
+    }
+    found
+}
+
+/// resolve 的只读版（编辑器预览用）：只读文件开头取宽高，srcset 里只放已经生成的缩小版；不编码、不写存储
+pub async fn peek(
+    settings: &Settings,
+    storage: &dyn Storage,
+    urls: &[String],
+) -> HashMap<String, Image> {
+    let mut found = HashMap::new();
+    for url in urls {
+        if url.contains(['?', '#']) {
+            continue;
+        }
+        let Some(key) = url.strip_prefix("/uploads/").and_then(storage::key) else { continue };
+        let head = match read_head(storage, key).await {
+            Ok(Some(head)) => head,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!("image {}: {}", url, e);
+                continue;
+            }
+        };
+        let Some((format, width, height)) = header(&head) else { continue };
+        let mut img = Image { width, height, variants: vec![] };
+        if processable(format) && !RE_VARIANT.is_match(url) {
+            let stem = key.rsplit_once('.').map_or(key, |(s, _)| s);
+            let existing: HashSet<String> = match storage.list(&format!("{}.", stem)).await {
+                Ok(files) => files.into_iter().map(|(k, _)| k).collect(),
+                Err(e) => {
+                    tracing::warn!("image variants {}: {}", url, e);
+                    HashSet::new()
+                }
+            };
+            // 和 inspect 一样：缺了的那一档及更宽的不放进 srcset
+            for v in plan(settings, key, width, format) {
+                if v.width.is_none() || v.format != format {
+                    continue;
+                }
+                let (Some(w), Some(variant_url)) =
+                    (v.width, variant_name(url, v.width, extension_of(url)))
+                else {
+                    break;
+                };
+                if !existing.contains(&v.name) {
+                    break;
+                }
+                img.variants.push((variant_url, w));
+            }
+        }
+        found.insert(url.clone(), img);
+    }
+    found
+}
+
+// 文件开头最多 HEAD_BYTES 字节（S3 上是分段请求）；文件不存在为 None
+async fn read_head(storage: &dyn Storage, key: &str) -> io::Result<Option<Vec<u8>>> {
+    let range = format!("bytes=0-{}", HEAD_BYTES - 1);
+    let Some(download) = storage.get_stream(key, &[("range", &range)]).await? else { return Ok(None) };
+    // 416：空文件
+    if !matches!(download.status, 200 | 206) {
+        return Ok(Some(vec![]));
+    }
+    let (mut body, mut head) = (download.body, vec![]);
+    while head.len() < HEAD_BYTES
+        && let Some(chunk) = body.next().await
+    {
+        head.extend_from_slice(&chunk?);
+    }
+    Ok(Some(head))
+}
+
+async fn inspect(
+    settings: &Settings,
+    storage: &dyn Storage,
+    key: &str,
+    url: &str,
+    data: Vec<u8>,
+) -> Option<Image> {
+    let (format, width, height) = header(&data)?;
+    let mut img = Image { width, height, variants: vec![] };
+    if !processable(format) || RE_VARIANT.is_match(url) {
+        return Some(img);
+    }
+
+    // 只要同格式的缩小版；生成失败的那一档及更宽的不放进 srcset
+    let wanted: Vec<Variant> = plan(settings, key, width, format)
+        .into_iter()
+        .filter(|v| v.width.is_some() && v.format == format)
+        .collect();
+    let failed = store_variants(storage, data, wanted.clone()).await;
+    for (name, e) in &failed {
+        tracing::warn!("image variant {}: {}", name, e);
+    }
+    for v in wanted {
+        let (Some(w), Some(variant_url)) =
+            (v.width, variant_name(url, v.width, extension_of(url)))
+        else {
+            break;
+        };
+        if failed.iter().any(|(name, _)| *name == v.name) {
+            break;
+        }
+        img.variants.push((variant_url, w));
+    }
+    Some(img)
+}
+
+/// 浏览器接受的现代格式里，已生成的那个（按 IMAGE_FORMATS 的偏好）；key 为存储里的相对路径
+/// 只替换 JPEG / PNG（及其缩小版），本身就是 WebP 的只会换成 AVIF
+pub async fn negotiate(
+    settings: &Settings,
+    storage: &dyn Storage,
+    key: &str,
+    accept: &str,
+) -> Option<String> {
+    let ext = extension_of(key).to_ascii_lowercase();
+    if !matches!(ext.as_str(), "jpg" | "png" | "webp") {
+        return None;
+    }
+    let stem = &key[..key.len() - ext.len() - 1];
+    for f in settings.formats.iter().filter(|f| f.ext() != ext && accepts(accept, f.mime())) {
+        let alt = format!("{}.{}", stem, f.ext());
+        if storage.exists(&alt).await.unwrap_or(false) {
+            return Some(alt);
+        }
+    }
+    None
+}
+
+/// Accept 里明确列出且 q 不为 0
+fn accepts(accept: &str, mime: &str) -> bool {
+    accept.split(',').any(|part| {
+        let mut it = part.split(';').map(str::trim);
+        it.next() == Some(mime)
+            && !it.any(|p| p.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0))
+    })
+}
+
+fn extension_of(name: &str) -> &str {
+    name.rsplit_once('.').map_or("", |(_, e)| e)
+}
+
+/// photo.jpg → photo.480w.jpg / photo.480w.webp / photo.avif；没有扩展名的不生成
+fn variant_name(name: &str, width: Option<u32>, ext: &str) -> Option<String> {
+    let (stem, orig) = name.rsplit_once('.')?;
+    if orig.contains('/') || stem.ends_with('/') {
+        return None;
+    }
+    Some(match width {
+        Some(w) => format!("{}.{}w.{}", stem, w, ext),
+        None => format!("{}.{}", stem, ext),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(format: ImageFormat) -> Vec<u8> {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(8, 8, |x, y| {
+            image::Rgb([(x * 30) as u8, (y * 30) as u8, 128])
+        }));
+        encode(&img, format).unwrap()
+    }
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|w| w == needle)
+    }
+
+    fn segment(marker: u8, body: &[u8]) -> Vec<u8> {
+        let mut s = vec![0xFF, marker];
+        s.extend_from_slice(&((body.len() + 2) as u16).to_be_bytes());
+        s.extend_from_slice(body);
+        s
+    }
+
+    #[test]
+    fn strips_jpeg_xmp_and_comments() {
+        let jpeg = sample(ImageFormat::Jpeg);
+        assert_eq!(prepare(&jpeg, false).unwrap(), None);
+
+        let icc = segment(0xE2, b"ICC_PROFILE\0\x01\x01");
+        let mut tagged = jpeg[..2].to_vec();
+        tagged.extend(segment(0xE1, b"http://ns.adobe.com/xap/1.0/\0<exif:GPSLatitude>1</exif:GPSLatitude>"));
+        tagged.extend(segment(0xFE, b"secret comment"));
+        tagged.extend(&icc);
+        tagged.extend(&jpeg[2..]);
+        tagged.extend(b"trailing MPF image");
+
+        let out = prepare(&tagged, false).unwrap().unwrap();
+        assert!(!contains(&out, b"GPSLatitude") && !contains(&out, b"secret") && !contains(&out, b"trailing"));
+        assert!(contains(&out, &icc));
+        let mut expected = jpeg[..2].to_vec();
+        expected.extend(&icc);
+        expected.extend(&jpeg[2..]);
+        assert_eq!(out, expected);
+        assert_eq!(prepare(&tagged, true).unwrap(), None);
+    }
+
+    #[test]
+    fn strips_png_text_chunks() {
+        let png = sample(ImageFormat::Png);
+        let text = b"XML:com.adobe.xmp\0GPS 1,2";
+        let mut chunk = (text.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(b"tEXt");
+        chunk.extend(text);
+        chunk.extend([0; 4]); // 解码器读到 IDAT 为止，不会校验后面的 CRC
+        let iend = png.len() - 12;
+        let mut tagged = png[..iend].to_vec();
+        tagged.extend(&chunk);
+        tagged.extend(&png[iend..]);
+        tagged.extend(b"trailing");
+        assert_eq!(prepare(&tagged, false).unwrap(), Some(png.clone()));
+        assert_eq!(prepare(&png, false).unwrap(), None);
+    }
+
+    #[test]
+    fn strips_webp_metadata_chunks() {
+        let simple = sample(ImageFormat::WebP);
+        let mut vp8x = b"VP8X".to_vec();
+        vp8x.extend(10u32.to_le_bytes());
+        vp8x.extend([0x08 | 0x04, 0, 0, 0, 7, 0, 0, 7, 0, 0]);
+        let mut exif = b"EXIF".to_vec();
+        exif.extend(5u32.to_le_bytes());
+        exif.extend(b"GPS!!\0"); // 奇数长度补齐
+        let mut xmp = b"XMP ".to_vec();
+        xmp.extend(4u32.to_le_bytes());
+        xmp.extend(b"<x/>");
+        let body = [vp8x, simple[12..].to_vec(), exif, xmp].concat();
+        let mut tagged = b"RIFF".to_vec();
+        tagged.extend(((body.len() + 4) as u32).to_le_bytes());
+        tagged.extend(b"WEBP");
+        tagged.extend(&body);
+
+        let out = prepare(&tagged, false).unwrap().unwrap();
+        assert!(!contains(&out, b"GPS") && !contains(&out, b"XMP "));
+        assert_eq!(out[20] & (0x08 | 0x04), 0);
+        assert_eq!(u32::from_le_bytes(out[4..8].try_into().unwrap()) as usize, out.len() - 8);
+        image::load_from_memory(&out).unwrap();
+    }
+}
@@ -0,0 +1,459 @@
+// 导入工具的公共部分：front matter 解析、日期归一化、本地图片收集、入库与链接改写、文章比对与写入
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+
+use crate::{
+    auth,
+    config::Config,
+    custom_fields,
+    db::Db,
+    images, markdown, media,
+    models::{new_id, now, PostInput},
+    posts,
+};
+
+/// 拆出 front matter：YAML 用 `---` 包围，TOML 用 `+++` 包围；没有则返回空对象
+pub fn split_front_matter(src: &str) -> anyhow::Result<(Map<String, Value>, String)> {
+    let src = src.trim_start_matches('\u{feff}');
+    for (fence, is_toml) in [("---", false), ("+++", true)] {
+        let Some(rest) = src.strip_prefix(fence) else { continue };
+        let Some(rest) = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n')) else {
+            continue;
+        };
+        // 找到单独成行的结束分隔符
+        let mut offset = 0;
+        for line in rest.split_inclusive('\n') {
+            if line.trim_end() == fence {
+                let head = &rest[..offset];
+                let body = rest[offset + line.len()..].to_string();
+                let meta = if is_toml {
+                    toml_to_json(toml::from_str::<toml::Value>(head)?)
+                } else if head.trim().is_empty() {
+                    Value::Object(Map::new())
+                } else {
+                    serde_yaml::from_str::<Value>(head)?
+                };
+                return match meta {
+                    Value::Object(m) => Ok((m, body)),
+                    Value::Null => Ok((Map::new(), body)),
+                    _ => anyhow::bail!("front matter is not a mapping"),
+                };
+            }
+            offset += line.len();
+        }
+        anyhow::bail!("unterminated front matter (missing closing `{}`)", fence);
+    }
+    Ok((Map::new(), src.to_string()))
+}
+
+fn toml_to_json(v: toml::Value) -> Value {
+    match v {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(t) => {
+            Value::Object(t.into_iter().map(|(k, v)| (k, toml_to_json(v))).collect())
+        }
+    }
+}
+
+/// 取字符串字段（数字等标量也转成字符串）
+pub fn meta_str(meta: &Map<String, Value>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|k| match meta.get(*k)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+pub fn meta_bool(meta: &Map<String, Value>, key: &str) -> Option<bool> {
+    match meta.get(key)? {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// 列表字段：既支持 [a, b] 也支持 "a, b"
+pub fn meta_list(meta: &Map<String, Value>, key: &str) -> Vec<String> {
+    match meta.get(key) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|v| match v {
+                Value::String(s) => Some(s.trim().to_string()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .filter(|s| !s.is_empty())
+            .collect(),
+        Some(Value::String(s)) => s
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// 各种常见日期写法 → UTC RFC3339（与库里其它时间戳同格式，便于按字符串排序）
+pub fn normalize_date(s: &str) -> Option<String> {
+    let s = s.trim();
+    if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+        return Some(d.with_timezone(&Utc).to_rfc3339());
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S %z", "%Y-%m-%d %H:%M %z", "%Y-%m-%dT%H:%M:%S%z"] {
+        if let Ok(d) = DateTime::parse_from_str(s, fmt) {
+            return Some(d.with_timezone(&Utc).to_rfc3339());
+        }
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y/%m/%d %H:%M:%S"] {
+        if let Ok(d) = NaiveDateTime::parse_from_str(s, fmt) {
+            return Some(d.and_utc().to_rfc3339());
+        }
+    }
+    for fmt in ["%Y-%m-%d", "%Y/%m/%d"] {
+        if let Ok(d) = NaiveDate::parse_from_str(s, fmt) {
+            return Some(d.and_hms_opt(0, 0, 0)?.and_utc().to_rfc3339());
+        }
+    }
+    None
+}
+
+static RE_MD_IMG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(!\[[^\]]*\]\(\s*)<?([^)\s>]+)>?((?:\s+"[^"]*")?\s*\))"#).unwrap());
+static RE_HTML_IMG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(<img\b[^>]*?\ssrc=")([^"]+)(")"#).unwrap());
+// 指向 /uploads/ 的普通链接（导出包里的 PDF 等附件）
+static RE_MD_UPLOAD: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(\]\(\s*)<?(/uploads/[^)\s>]+)>?((?:\s+"[^"]*")?\s*\))"#).unwrap());
+
+/// 正文中引用的本地图片和上传附件（跳过 http(s)、协议相对、data: 等远程 / 内联地址）
+pub fn local_image_refs(md: &str) -> Vec<String> {
+    let mut out = vec![];
+    for re in [&*RE_MD_IMG, &*RE_HTML_IMG, &*RE_MD_UPLOAD] {
+        for c in re.captures_iter(md) {
+            let url = c[2].to_string();
+            if is_local(&url) && !out.contains(&url) {
+                out.push(url);
+            }
+        }
+    }
+    out
+}
+
+fn is_local(url: &str) -> bool {
+    !(url.contains("://") || url.starts_with("//") || url.starts_with("data:") || url.starts_with('#'))
+}
+
+/// 解析图片引用的磁盘路径：以 / 开头的相对站点根目录（Hugo static / Hexo source），其余相对 md 文件
+pub fn resolve_local(url: &str, md_dir: &Path, site_root: &Path) -> PathBuf {
+    let clean = url.split(['?', '#']).next().unwrap_or(url).replace("%20", " ");
+    match clean.strip_prefix('/') {
+        Some(abs) => site_root.join(abs),
+        None => md_dir.join(clean),
+    }
+}
+
+/// 导入的本地文件：和后台上传一样检查类型、大小和内容，图片摆正并去掉元数据，按处理后内容的哈希存放
+pub struct Upload {
+    pub src: PathBuf,
+    pub ext: String,
+    pub hash: String,
+    pub url: String,
+    pub filename: String,
+    pub size: i64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+// 读取并处理文件，得到要存的内容（计划和写入各做一次，不把所有文件留在内存里）
+fn prepare_upload(src: &Path, cfg: &Config) -> anyhow::Result<(String, Vec<u8>)> {
+    let name = src.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+    let ext = media::extension(name).map_err(anyhow::Error::msg)?;
+    let kind = media::Kind::of(&ext);
+    let limit = cfg.upload_limits.of(kind);
+    if fs::metadata(src)?.len() > limit {
+        anyhow::bail!("{} files are limited to {}", kind.name(), media::human_size(limit));
+    }
+    let data = fs::read(src)?;
+    media::check_content(&ext, &data).map_err(anyhow::Error::msg)?;
+    let data = images::prepare(&data, cfg.images.keep_exif)
+        .map_err(anyhow::Error::msg)?
+        .unwrap_or(data);
+    Ok((ext, data))
+}
+
+/// 检查一个本地文件并算出它导入后的 /uploads/ 地址（不写入）
+pub fn plan_upload(src: &Path, cfg: &Config) -> anyhow::Result<Upload> {
+    let (ext, data) = prepare_upload(src, cfg)?;
+    let hash = media::hash(&data);
+    let (width, height) = media::dimensions(&data).unzip();
+    Ok(Upload {
+        src: src.to_path_buf(),
+        url: media::url(&hash, &ext),
+        filename: media::display_name(&src.to_string_lossy()),
+        size: data.len() as i64,
+        ext,
+        hash,
+        width,
+        height,
+    })
+}
+
+/// 把文件写进上传存储（已存在则跳过），新写入的图片同时生成缩小版；返回生成失败的派生文件及原因
+pub async fn store_upload(cfg: &Config, u: &Upload) -> anyhow::Result<Vec<(String, String)>> {
+    let (ext, data) = prepare_upload(&u.src, cfg)?;
+    if media::hash(&data) != u.hash {
+        anyhow::bail!("{} 在生成计划后被改动，请重新运行", u.src.display());
+    }
+    let (_, created) = media::store(&*cfg.storage, &ext, &data).await?;
+    if !created || u.width.is_none() {
+        return Ok(vec![]);
+    }
+    Ok(images::generate(&cfg.images, &*cfg.storage, &media::rel_path(&u.hash, &ext), data).await)
+}
+
+/// 把文章引用的导入文件登记到文章作者的媒体库（同一作者同一内容只登记一次）
+pub async fn register_uploads(pool: &Db, post_id: &str, urls: &[String], uploads: &[Upload]) -> anyhow::Result<()> {
+    let owner_id = sqlx::query_scalar!("SELECT author_id FROM posts WHERE id = ?", post_id)
+        .fetch_one(pool)
+        .await?;
+    for u in uploads.iter().filter(|u| urls.contains(&u.url)) {
+        let (id, ts, mime) = (new_id(), now(), media::mime(&u.ext));
+        sqlx::query!(
+            "INSERT OR IGNORE INTO media
+               (id, owner_id, hash, ext, filename, size, created_at, mime, width, height)
+             VALUES (?,?,?,?,?,?,?,?,?,?)",
+            id,
+            owner_id,
+            u.hash,
+            u.ext,
+            u.filename,
+            u.size,
+            ts,
+            mime,
+            u.width,
+            u.height
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// 按映射表改写正文里的图片链接
+pub fn rewrite_images(md: &str, map: &HashMap<String, String>) -> String {
+    let mut out = md.to_string();
+    for re in [&*RE_MD_IMG, &*RE_HTML_IMG, &*RE_MD_UPLOAD] {
+        out = re
+            .replace_all(&out, |c: &Captures| match map.get(&c[2]) {
+                Some(new) => format!("{}{}{}", &c[1], new, &c[3]),
+                None => c[0].to_string(),
+            })
+            .into_owned();
+    }
+    out
+}
+
+/// 已有文章与导入内容的差异（标题 / 正文 / 摘要 / 状态 / 标签，以及导入里给出的可见性 / 密码 / 语言 / 自定义字段），
+/// 为空表示无需更新
+pub async fn changes(pool: &Db, post_id: &str, inp: &PostInput) -> anyhow::Result<Vec<String>> {
+    let old = sqlx::query!(
+        "SELECT title, body_md, excerpt, status, visibility, password_hash, lang, custom_fields
+           FROM posts WHERE id = ?",
+        post_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let mut old_tags: Vec<String> = sqlx::query_scalar!(
+        "SELECT t.name FROM tags t JOIN post_tags pt ON pt.tag_id = t.id WHERE pt.post_id = ?",
+        post_id
+    )
+    .fetch_all(pool)
+    .await?;
+    old_tags.sort();
+    let mut new_tags = inp.tags.clone().unwrap_or_default();
+    new_tags.sort();
+
+    let mut changes = vec![];
+    if old.title != inp.title {
+        changes.push("title".to_string());
+    }
+    if old.body_md != inp.body_md {
+        let (added, removed) = line_delta(&old.body_md, &inp.body_md);
+        changes.push(format!("body (+{}/-{} lines)", added, removed));
+    }
+    if old.excerpt != inp.excerpt {
+        changes.push("excerpt".into());
+    }
+    if Some(&old.status) != inp.status.as_ref() {
+        changes.push(format!("status {} -> {}", old.status, inp.status.as_deref().unwrap_or("draft")));
+    }
+    if old_tags != new_tags {
+        changes.push("tags".into());
+    }
+    if let Some(v) = &inp.visibility
+        && *v != old.visibility
+    {
+        changes.push(format!("visibility {} -> {}", old.visibility, v));
+    }
+    if let Some(pw) = inp.password.as_deref().filter(|p| !p.is_empty())
+        && !old.password_hash.as_deref().is_some_and(|h| auth::verify_password(pw, h))
+    {
+        changes.push("password".into());
+    }
+    if let Some(lang) = &inp.lang
+        && *lang != old.lang
+    {
+        changes.push(format!("lang {} -> {}", old.lang, lang));
+    }
+    if let Some(fields) = &inp.custom_fields
+        && serde_json::from_str::<Map<String, Value>>(&old.custom_fields).ok().as_ref() != Some(fields)
+    {
+        changes.push("custom_fields".into());
+    }
+    Ok(changes)
+}
+
+/// 粗略的行级差异：新增 / 删除的行数
+fn line_delta(old: &str, new: &str) -> (i64, i64) {
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for l in old.lines() {
+        *counts.entry(l).or_default() -= 1;
+    }
+    for l in new.lines() {
+        *counts.entry(l).or_default() += 1;
+    }
+    let added = counts.values().filter(|c| **c > 0).sum();
+    let removed = -counts.values().filter(|c| **c < 0).sum::<i64>();
+    (added, removed)
+}
+
+/// 按导入内容更新已有文章（保留 id、作者、slug；可见性 / 语言 / 自定义字段导入里没给时沿用原值）
+pub async fn overwrite_post(
+    pool: &Db,
+    cfg: &Config,
+    id: &str,
+    inp: &PostInput,
+    ts: &posts::Timestamps,
+) -> anyhow::Result<()> {
+    // 和 insert_post 一样校验
+    if let Some(v) = &inp.visibility {
+        posts::check_visibility(v)?;
+    }
+    if let Some(lang) = &inp.lang {
+        posts::check_lang(lang)?;
+    }
+    let fields_json = match &inp.custom_fields {
+        Some(fields) => {
+            custom_fields::validate(fields, cfg.custom_fields.as_ref())?;
+            Some(Value::Object(fields.clone()).to_string())
+        }
+        None => None,
+    };
+    // protected：给了新密码则替换，否则沿用原密码（原来没有则报错）；改成其它可见性时清空密码
+    let password_hash = match inp.password.as_deref() {
+        Some(pw) if inp.visibility.as_deref() == Some("protected") && !pw.is_empty() => {
+            Some(auth::hash_password(pw)?)
+        }
+        _ => None,
+    };
+    if inp.visibility.as_deref() == Some("protected") && password_hash.is_none() {
+        let has_password = sqlx::query_scalar!("SELECT password_hash IS NOT NULL FROM posts WHERE id = ?", id)
+            .fetch_one(pool)
+            .await?;
+        if has_password == 0 {
+            anyhow::bail!("protected post requires a password");
+        }
+    }
+
+    let overrides = match &inp.markdown_options {
+        Some(o) => Some(o.clone()),
+        None => posts::stored_overrides(pool, id).await?,
+    };
+    let author_id = sqlx::query_scalar!("SELECT author_id FROM posts WHERE id = ?", id)
+        .fetch_one(pool)
+        .await?;
+    let d = posts::derive(&mut *pool.acquire().await?, &inp.body_md, cfg, &author_id, overrides.as_ref())
+        .await?;
+    let render_version = markdown::RENDERER_VERSION;
+    let overrides_json = posts::overrides_json(inp);
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE posts SET
+            title           = ?,
+            excerpt         = ?,
+            body_md         = ?,
+            body_html       = ?,
+            toc             = ?,
+            status          = ?,
+            visibility      = COALESCE(?, visibility),
+            password_hash   = CASE WHEN COALESCE(?, visibility) = 'protected' THEN COALESCE(?, password_hash) END,
+            custom_fields   = COALESCE(?, custom_fields),
+            lang            = COALESCE(?, lang),
+            word_count      = ?,
+            reading_minutes = ?,
+            auto_excerpt    = ?,
+            markdown_options = COALESCE(?, markdown_options),
+            render_options  = ?,
+            render_version  = ?,
+            published_at    = COALESCE(?, published_at),
+            updated_at      = ?
+        WHERE id = ?
+        "#,
+        inp.title,
+        inp.excerpt,
+        inp.body_md,
+        d.html,
+        d.toc_json,
+        inp.status,
+        inp.visibility,
+        // SET 右边的列读到的是旧值，这里按新的可见性判断
+        inp.visibility,
+        password_hash,
+        fields_json,
+        inp.lang,
+        d.stats.word_count,
+        d.stats.reading_minutes,
+        d.stats.excerpt,
+        overrides_json,
+        d.options_json,
+        render_version,
+        ts.published_at,
+        ts.updated_at,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    posts::set_tags(&mut tx, id, inp.tags.as_deref().unwrap_or_default()).await?;
+    posts::save_links(&mut tx, id, &d.links).await?;
+    posts::save_media(&mut tx, id, &d.media).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn create_post(
+    pool: &Db,
+    cfg: &Config,
+    author_id: &str,
+    inp: &PostInput,
+    ts: &posts::Timestamps,
+) -> anyhow::Result<String> {
+    let mut tx = pool.begin().await?;
+    let (id, ..) = posts::insert_post(&mut tx, cfg, author_id, inp, ts).await?;
+    tx.commit().await?;
+    Ok(id)
+}
@@ -0,0 +1,28 @@
+// 供 main.rs 与 src/bin 下的命令行工具共用
+pub mod auth;
+pub mod backup;
+pub mod config;
+pub mod custom_fields;
+pub mod db;
+pub mod error;
+pub mod exporter;
+pub mod highlight;
+pub mod images;
+pub mod importer;
+pub mod markdown;
+pub mod math;
+pub mod media;
+pub mod models;
+pub mod posts;
+pub mod rerender;
+pub mod routes;
+pub mod sanitize;
+pub mod shortcodes;
+pub mod rss;
+pub mod ssg;
+pub mod state;
+pub mod storage;
+#[cfg(test)]
+mod testing;
+pub mod throttle;
+pub mod wxr;
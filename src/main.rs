@@ -1,15 +1,7 @@
 use std::net::SocketAddr;
 use tracing_subscriber::{EnvFilter, fmt::Subscriber};
 
-mod auth;
-mod config;
-mod db;
-mod error;
-mod markdown;
-mod models;
-mod routes;
-mod rss;
-mod state;
+use blog_backend::{config, db, routes, state};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -52,6 +44,8 @@ async fn main() -> anyhow::Result<()> {
         db: pool.clone(),
         cfg: cfg.clone(),
         jwt_secret: cfg.jwt_secret.clone(),
+        rerender: Default::default(),
+        unlock_throttle: Default::default(),
     };
 
     // 6) 构建路由（routes::app_router 内部已 .with_state(app_state)）
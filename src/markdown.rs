@@ -1,11 +1,658 @@
-use comrak::{markdown_to_html, ComrakOptions};
-
-pub fn render(md: &str) -> String {
-    let mut opts = ComrakOptions::default();
-    opts.extension.strikethrough = true;
-    opts.extension.table = true;
-    opts.extension.autolink = true;
-    opts.extension.tasklist = true;
-    opts.render.unsafe_ = true;
-    markdown_to_html(md, &opts)
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    sync::Mutex,
+};
+
+use comrak::{
+    adapters::{HeadingAdapter, HeadingMeta},
+    arena_tree::Node,
+    format_html_with_plugins,
+    nodes::{Ast, AstNode, LineColumn, NodeHtmlBlock, NodeValue, Sourcepos},
+    parse_document, Arena, ComrakOptions, ComrakPlugins,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{highlight::Highlighter, images::Image, math, sanitize, shortcodes};
+
+/// 渲染管线版本：改动会影响输出时（新扩展、清洗规则、高亮…）加一，
+/// render_version 低于它的文章由 rerender 重新渲染
+pub const RENDERER_VERSION: i64 = 2;
+
+/// 渲染结果：HTML + 目录 + 站内链接
+pub struct Rendered {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    pub links: Vec<String>,      // [[...]] 的目标（去重，按出现顺序）
+    pub unresolved: Vec<String>, // 其中没找到文章的
+    pub hrefs: Vec<String>,      // 普通链接的地址（去重），用来检查站内链接
+    pub missing_alt: Vec<String>, // 没写替代文字的图片地址
+}
+
+/// [[target]] 解析到的文章
+pub struct LinkTarget {
+    pub url: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TocEntry {
+    pub level: u8,
+    pub text: String,
+    pub anchor: String,
+}
+
+/// 渲染选项：站点默认值来自 MARKDOWN_OPTIONS，单篇文章可覆盖；实际生效的一组随文章保存
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    pub strikethrough: bool,
+    pub table: bool,
+    pub autolink: bool,
+    pub tasklist: bool,
+    pub math: bool,              // $...$ / $$...$$ / ```math
+    pub footnotes: bool,         // [^1]
+    pub description_lists: bool, // 术语\n\n: 解释
+    pub superscript: bool,       // e = mc^2^
+    pub header_ids: bool,        // 标题带锚点 id
+    pub front_matter: bool,      // 去掉开头的 --- 块
+    pub smart_punctuation: bool, // 弯引号、破折号、省略号
+    pub alerts: bool,            // > [!NOTE] 等 GitHub 风格提示块
+    pub shortcodes: bool,        // {{< youtube id >}} 等
+    pub wikilinks: bool,         // [[slug]] / [[slug|文字]] 站内链接
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            strikethrough: true,
+            table: true,
+            autolink: true,
+            tasklist: true,
+            math: true,
+            footnotes: true,
+            description_lists: false,
+            superscript: false,
+            header_ids: true,
+            front_matter: true,
+            smart_punctuation: false,
+            alerts: true,
+            shortcodes: true,
+            wikilinks: true,
+        }
+    }
+}
+
+impl Options {
+    /// MARKDOWN_OPTIONS=superscript,smart_punctuation,-footnotes：在默认值上打开 / 关闭（- 开头为关闭）
+    pub fn parse_list(s: &str) -> Result<Self, String> {
+        let mut overrides = Map::new();
+        for item in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (name, on) = match item.strip_prefix('-') {
+                Some(name) => (name, false),
+                None => (item.strip_prefix('+').unwrap_or(item), true),
+            };
+            overrides.insert(name.to_string(), Value::Bool(on));
+        }
+        Self::default().with(&overrides)
+    }
+
+    /// 按 {"footnotes": false, ...} 覆盖部分选项；未知选项或非布尔值报错
+    pub fn with(&self, overrides: &Map<String, Value>) -> Result<Self, String> {
+        let Ok(Value::Object(mut all)) = serde_json::to_value(self) else { unreachable!() };
+        for (k, v) in overrides {
+            if !all.contains_key(k) {
+                return Err(format!("unknown markdown option `{}`", k));
+            }
+            if !v.is_boolean() {
+                return Err(format!("markdown option `{}` must be true or false", k));
+            }
+            all.insert(k.clone(), v.clone());
+        }
+        serde_json::from_value(Value::Object(all)).map_err(|e| e.to_string())
+    }
+
+    fn comrak(&self) -> ComrakOptions {
+        let mut opts = ComrakOptions::default();
+        opts.extension.strikethrough = self.strikethrough;
+        opts.extension.table = self.table;
+        opts.extension.autolink = self.autolink;
+        opts.extension.tasklist = self.tasklist;
+        opts.extension.math_dollars = self.math;
+        opts.extension.footnotes = self.footnotes;
+        opts.extension.description_lists = self.description_lists;
+        opts.extension.superscript = self.superscript;
+        opts.extension.front_matter_delimiter = self.front_matter.then(|| "---".into());
+        opts.extension.wikilinks_title_after_pipe = self.wikilinks;
+        opts.parse.smart = self.smart_punctuation;
+        opts.render.unsafe_ = true;
+        opts.render.full_info_string = true; // 语言后的 {3,5-7} 交给高亮器
+        opts
+    }
+}
+
+/// 展开短代码并解析成 AST
+fn parse<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    md: &str,
+    options: &Options,
+    shortcodes: &shortcodes::Registry,
+) -> &'a AstNode<'a> {
+    if options.shortcodes {
+        parse_document(arena, &shortcodes::expand(md, shortcodes), &options.comrak())
+    } else {
+        parse_document(arena, md, &options.comrak())
+    }
+}
+
+/// 正文里 [[...]] 的目标（渲染前先拿去查库）
+pub fn wiki_targets(md: &str, options: &Options, shortcodes: &shortcodes::Registry) -> Vec<String> {
+    if !options.wikilinks {
+        return vec![];
+    }
+    let arena = Arena::new();
+    let mut targets: Vec<String> = vec![];
+    for node in parse(&arena, md, options, shortcodes).descendants() {
+        if let NodeValue::WikiLink(l) = &node.data.borrow().value {
+            let t = l.url.trim().to_string();
+            if !t.is_empty() && !targets.contains(&t) {
+                targets.push(t);
+            }
+        }
+    }
+    targets
+}
+
+/// 正文里指向 /uploads/ 的图片地址（渲染前先读出宽高、生成缩小版）
+pub fn image_urls(md: &str, options: &Options, shortcodes: &shortcodes::Registry) -> Vec<String> {
+    let arena = Arena::new();
+    let mut urls: Vec<String> = vec![];
+    for node in parse(&arena, md, options, shortcodes).descendants() {
+        if let NodeValue::Image(l) = &node.data.borrow().value
+            && l.url.starts_with("/uploads/")
+            && !urls.contains(&l.url)
+        {
+            urls.push(l.url.clone());
+        }
+    }
+    urls
+}
+
+/// 渲染并按 policy 清洗（正文里允许写原始 HTML，最后统一过白名单）
+/// links：wiki_targets 查到的文章；不在其中的 [[...]] 渲染成 wikilink-missing
+/// images：image_urls 读到的图片；/uploads/ 下的图片一律懒加载，读到的再带上宽高和 srcset
+pub fn render(
+    md: &str,
+    options: &Options,
+    shortcodes: &shortcodes::Registry,
+    links: &HashMap<String, LinkTarget>,
+    images: &HashMap<String, Image>,
+    policy: &sanitize::Policy,
+) -> Rendered {
+    let opts = options.comrak();
+
+    let headings = Headings { ids: options.header_ids, ..Default::default() };
+    let highlighter = Highlighter::default();
+    let mut plugins = ComrakPlugins::default();
+    plugins.render.heading_adapter = Some(&headings);
+    plugins.render.codefence_syntax_highlighter = Some(&highlighter);
+
+    let arena = Arena::new();
+    let root = parse(&arena, md, options, shortcodes);
+    // 公式节点换成 MathML（raw HTML 节点照原样输出）
+    if options.math {
+        for node in root.descendants() {
+            let mut ast = node.data.borrow_mut();
+            let html = match &ast.value {
+                NodeValue::Math(m) => NodeValue::HtmlInline(math::to_mathml(&m.literal, m.display_math)),
+                NodeValue::CodeBlock(cb) if cb.info.trim() == "math" => html_block(format!(
+                    "{}\n",
+                    math::to_mathml(&cb.literal, true)
+                )),
+                _ => continue,
+            };
+            ast.value = html;
+        }
+    }
+    if options.alerts {
+        let quotes: Vec<_> = root
+            .descendants()
+            .filter(|n| matches!(n.data.borrow().value, NodeValue::BlockQuote))
+            .collect();
+        for quote in quotes {
+            alert(&arena, quote);
+        }
+    }
+    let (mut all, mut unresolved) = (vec![], vec![]);
+    let wikilinks: Vec<_> = root
+        .descendants()
+        .filter(|n| matches!(n.data.borrow().value, NodeValue::WikiLink(_)))
+        .collect();
+    for node in wikilinks {
+        let target = match &node.data.borrow().value {
+            NodeValue::WikiLink(l) => l.url.trim().to_string(),
+            _ => continue,
+        };
+        if !all.contains(&target) {
+            all.push(target.clone());
+        }
+        wiki_link(node, &target, links.get(&target));
+        if !links.contains_key(&target) && !unresolved.contains(&target) {
+            unresolved.push(target);
+        }
+    }
+    let (mut hrefs, mut missing_alt) = (vec![], vec![]);
+    for node in root.descendants() {
+        match &node.data.borrow().value {
+            NodeValue::Link(l) if !hrefs.contains(&l.url) => hrefs.push(l.url.clone()),
+            NodeValue::Image(l) if node.descendants().skip(1).all(|c| is_blank(c)) => {
+                missing_alt.push(l.url.clone())
+            }
+            _ => {}
+        }
+    }
+    let uploads: Vec<_> = root
+        .descendants()
+        .filter(|n| matches!(&n.data.borrow().value, NodeValue::Image(l) if l.url.starts_with("/uploads/")))
+        .collect();
+    for node in uploads {
+        responsive_image(node, images);
+    }
+
+    let mut out = vec![];
+    format_html_with_plugins(root, &opts, &mut out, &plugins).expect("writing to Vec cannot fail");
+    let html = policy.clean(&String::from_utf8_lossy(&out));
+    let toc = headings.into_toc();
+    Rendered { html, toc, links: all, unresolved, hrefs, missing_alt }
+}
+
+/// [[slug]] → 指向文章的链接（没写文字时用文章标题）；找不到的换成带提示的 <span>
+fn wiki_link<'a>(node: &'a AstNode<'a>, target: &str, found: Option<&LinkTarget>) {
+    let label: String = node
+        .children()
+        .map(|c| match &c.data.borrow().value {
+            NodeValue::Text(t) => t.clone(),
+            _ => String::new(),
+        })
+        .collect();
+    match found {
+        Some(t) => {
+            if let NodeValue::WikiLink(l) = &mut node.data.borrow_mut().value {
+                l.url = t.url.clone();
+            }
+            if label.trim() == target
+                && let Some(text) = node.first_child()
+                && let NodeValue::Text(s) = &mut text.data.borrow_mut().value
+            {
+                *s = t.title.clone();
+            }
+        }
+        None => {
+            for c in node.children().collect::<Vec<_>>() {
+                c.detach();
+            }
+            node.data.borrow_mut().value = NodeValue::HtmlInline(format!(
+                "<span class=\"wikilink-missing\" title=\"no post `{}`\">{}</span>",
+                escape(target),
+                escape(&label)
+            ));
+        }
+    }
+}
+
+/// ![alt](/uploads/a.jpg "标题") → <img loading="lazy" width height srcset …>；
+/// 有标题且图片独占一段时换成 <figure>，标题作 <figcaption>
+fn responsive_image<'a>(node: &'a AstNode<'a>, images: &HashMap<String, Image>) {
+    let (url, title) = match &node.data.borrow().value {
+        NodeValue::Image(l) => (l.url.clone(), l.title.clone()),
+        _ => return,
+    };
+    let alt: String = node
+        .descendants()
+        .map(|c| match &c.data.borrow().value {
+            NodeValue::Text(t) => t.clone(),
+            NodeValue::Code(c) => c.literal.clone(),
+            _ => String::new(),
+        })
+        .collect();
+    let mut tag = format!("<img src=\"{}\" alt=\"{}\"", escape(&url), escape(&alt));
+    if let Some(img) = images.get(&url) {
+        tag.push_str(&format!(" width=\"{}\" height=\"{}\"", img.width, img.height));
+        if !img.variants.is_empty() {
+            let srcset: Vec<String> = img
+                .variants
+                .iter()
+                .map(|(u, w)| format!("{} {}w", escape(u), w))
+                .chain(std::iter::once(format!("{} {}w", escape(&url), img.width)))
+                .collect();
+            tag.push_str(&format!(
+                " srcset=\"{}\" sizes=\"(max-width: {w}px) 100vw, {w}px\"",
+                srcset.join(", "),
+                w = img.width
+            ));
+        }
+    }
+    tag.push_str(" loading=\"lazy\" decoding=\"async\"");
+
+    for c in node.children().collect::<Vec<_>>() {
+        c.detach();
+    }
+    // 段落里除了这张图只有空白
+    let parent = node.parent().filter(|p| {
+        matches!(p.data.borrow().value, NodeValue::Paragraph)
+            && p.children().all(|c| std::ptr::eq(c, node) || is_blank(c))
+    });
+    match parent {
+        Some(para) if !title.is_empty() => {
+            for c in para.children().collect::<Vec<_>>() {
+                c.detach();
+            }
+            para.data.borrow_mut().value = html_block(format!(
+                "<figure>{}><figcaption>{}</figcaption></figure>\n",
+                tag,
+                escape(&title)
+            ));
+        }
+        _ => {
+            if !title.is_empty() {
+                tag.push_str(&format!(" title=\"{}\"", escape(&title)));
+            }
+            node.data.borrow_mut().value = NodeValue::HtmlInline(format!("{}>", tag));
+        }
+    }
+}
+
+/// 空白文本或换行
+fn is_blank<'a>(node: &'a AstNode<'a>) -> bool {
+    match &node.data.borrow().value {
+        NodeValue::Text(t) => t.trim().is_empty(),
+        NodeValue::SoftBreak | NodeValue::LineBreak => true,
+        _ => false,
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn html_block(literal: String) -> NodeValue {
+    NodeValue::HtmlBlock(NodeHtmlBlock { block_type: 0, literal })
+}
+
+static RE_ALERT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^\[!(note|tip|important|warning|caution)\]\s*$").unwrap());
+
+/// 首行是 [!NOTE] 之类的引用块 → <div class="markdown-alert markdown-alert-note">（与 GitHub 的类名一致）
+fn alert<'a>(arena: &'a Arena<AstNode<'a>>, quote: &'a AstNode<'a>) {
+    let Some(para) = quote.first_child() else { return };
+    if !matches!(para.data.borrow().value, NodeValue::Paragraph) {
+        return;
+    }
+    // 首行可能被拆成几个文本节点（[、!NOTE、]），拼起来再判断
+    let mut marker = String::new();
+    let mut first_line = vec![];
+    for n in para.children() {
+        match &n.data.borrow().value {
+            NodeValue::Text(t) => marker.push_str(t),
+            NodeValue::SoftBreak | NodeValue::LineBreak => {
+                first_line.push(n);
+                break;
+            }
+            _ => return,
+        }
+        first_line.push(n);
+    }
+    let Some(c) = RE_ALERT.captures(&marker) else { return };
+    let kind = c[1].to_lowercase();
+
+    for n in first_line {
+        n.detach();
+    }
+    if para.first_child().is_none() {
+        para.detach();
+    }
+    let mut title = kind.clone();
+    title[..1].make_ascii_uppercase();
+    quote.data.borrow_mut().value = html_block(format!(
+        "<div class=\"markdown-alert markdown-alert-{}\">\n<p class=\"markdown-alert-title\">{}</p>\n",
+        kind, title
+    ));
+    let close = arena.alloc(Node::new(RefCell::new(Ast::new(
+        html_block("</div>\n".into()),
+        LineColumn { line: 0, column: 0 },
+    ))));
+    quote.insert_after(close);
+}
+
+/// 数据库中的目录 JSON → Value（损坏时退化为空数组）
+pub fn parse_toc(raw: &str) -> serde_json::Value {
+    serde_json::from_str::<serde_json::Value>(raw)
+        .ok()
+        .filter(serde_json::Value::is_array)
+        .unwrap_or_else(|| serde_json::json!([]))
+}
+
+/// 标题适配器：给 <hN> 加上唯一 id，同时收集目录
+#[derive(Default)]
+struct Headings {
+    ids: bool,
+    state: Mutex<HeadingState>,
+}
+
+#[derive(Default)]
+struct HeadingState {
+    used: HashSet<String>,
+    toc: Vec<TocEntry>,
+}
+
+impl Headings {
+    fn into_toc(self) -> Vec<TocEntry> {
+        self.state.into_inner().map(|s| s.toc).unwrap_or_default()
+    }
+}
+
+impl HeadingAdapter for Headings {
+    fn enter(
+        &self,
+        output: &mut dyn Write,
+        heading: &HeadingMeta,
+        _sourcepos: Option<Sourcepos>,
+    ) -> io::Result<()> {
+        let mut st = self.state.lock().unwrap();
+        let text = heading.content.trim().to_string();
+        let anchor = unique_anchor(&mut st.used, &text);
+        if self.ids {
+            write!(output, "<h{} id=\"{}\">", heading.level, anchor)?;
+        } else {
+            write!(output, "<h{}>", heading.level)?;
+        }
+        st.toc.push(TocEntry { level: heading.level, text, anchor });
+        Ok(())
+    }
+
+    fn exit(&self, output: &mut dyn Write, heading: &HeadingMeta) -> io::Result<()> {
+        writeln!(output, "</h{}>", heading.level)
+    }
+}
+
+/// 标题 → 锚点：保留各语言的字母/数字（中文标题也可用），空白变 '-'，重复时追加 -1、-2…
+fn unique_anchor(used: &mut HashSet<String>, text: &str) -> String {
+    let mut base = String::new();
+    for c in text.to_lowercase().chars() {
+        if c.is_alphanumeric() || c == '_' {
+            base.push(c);
+        } else if (c.is_whitespace() || c == '-') && !base.ends_with('-') {
+            base.push('-');
+        }
+    }
+    let base = base.trim_matches('-');
+    let base = if base.is_empty() { "section" } else { base };
+
+    let mut anchor = base.to_string();
+    let mut n = 0;
+    while used.contains(&anchor) {
+        n += 1;
+        anchor = format!("{}-{}", base, n);
+    }
+    used.insert(anchor.clone());
+    anchor
+}
+
+// 阅读速度：中日韩按字、其它按词
+const CJK_CHARS_PER_MIN: f64 = 300.0;
+const WORDS_PER_MIN: f64 = 200.0;
+const EXCERPT_CHARS: usize = 140;
+
+/// 正文统计（基于渲染后的 HTML）
+pub struct TextStats {
+    pub word_count: i64,
+    pub reading_minutes: i64,
+    pub excerpt: Option<String>,
+}
+
+static RE_SKIP: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<(script|style)\b.*?</(script|style)>").unwrap());
+static RE_NOT_PROSE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<pre\b.*?</pre>|<h[1-6]\b.*?</h[1-6]>").unwrap());
+static RE_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+static RE_WS: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+").unwrap());
+
+pub fn text_stats(html: &str) -> TextStats {
+    let html = RE_SKIP.replace_all(html, " ");
+    let text = plain_text(&html);
+
+    let (mut cjk, mut words, mut in_word) = (0i64, 0i64, false);
+    for c in text.chars() {
+        if is_cjk(c) {
+            cjk += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                words += 1;
+            }
+            in_word = true;
+        } else if c != '\'' && c != '-' {
+            in_word = false;
+        }
+    }
+
+    let minutes = (cjk as f64 / CJK_CHARS_PER_MIN + words as f64 / WORDS_PER_MIN).ceil() as i64;
+
+    // 摘要不取代码块和标题
+    let prose = plain_text(&RE_NOT_PROSE.replace_all(&html, " "));
+    let excerpt = if prose.is_empty() {
+        None
+    } else if prose.chars().count() <= EXCERPT_CHARS {
+        Some(prose)
+    } else {
+        let cut: String = prose.chars().take(EXCERPT_CHARS).collect();
+        Some(format!("{}…", cut.trim_end()))
+    };
+
+    TextStats {
+        word_count: cjk + words,
+        reading_minutes: if cjk + words > 0 { minutes.max(1) } else { 0 },
+        excerpt,
+    }
+}
+
+fn plain_text(html: &str) -> String {
+    let s = RE_TAG.replace_all(html, " ");
+    let s = s
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    RE_WS.replace_all(s.trim(), " ").into_owned()
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // 平假名 / 片假名
+        | 0x3400..=0x4DBF   // CJK 扩展 A
+        | 0x4E00..=0x9FFF   // CJK 统一表意文字
+        | 0xAC00..=0xD7AF   // 谚文音节
+        | 0xF900..=0xFAFF   // CJK 兼容表意文字
+        | 0x20000..=0x2FA1F // CJK 扩展 B 及之后
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(md: &str) -> Rendered {
+        let policy = sanitize::Policy {
+            extra_tags: vec![],
+            extra_attrs: vec![],
+            url_schemes: vec!["https".into()],
+            embed_hosts: vec![],
+            site_host: None,
+        };
+        render(md, &Options::default(), &Default::default(), &HashMap::new(), &HashMap::new(), &policy)
+    }
+
+    #[test]
+    fn heading_ids_are_unique() {
+        let r = html("# Intro\n\n## Intro\n\n## Intro\n\n### 中文 标题！\n\n## Intro-1\n\n## ???\n");
+        let anchors: Vec<_> = r.toc.iter().map(|e| e.anchor.as_str()).collect();
+        assert_eq!(anchors, ["intro", "intro-1", "intro-2", "中文-标题", "intro-1-1", "section"]);
+        let levels: Vec<_> = r.toc.iter().map(|e| e.level).collect();
+        assert_eq!(levels, [1, 2, 2, 3, 2, 2]);
+        assert_eq!(r.toc[3].text, "中文 标题！");
+        assert!(r.html.contains("<h2 id=\"intro-2\">Intro</h2>"), "{}", r.html);
+        assert!(r.html.contains("<h3 id=\"中文-标题\">"), "{}", r.html);
+    }
+
+    #[test]
+    fn toc_round_trip() {
+        let r = html("## A *b* `c`\n");
+        assert_eq!(r.toc[0].text, "A b c");
+        let json = serde_json::to_string(&r.toc).unwrap();
+        assert_eq!(parse_toc(&json)[0]["anchor"], "a-b-c");
+        assert_eq!(parse_toc("not json"), serde_json::json!([]));
+        assert_eq!(parse_toc("{}"), serde_json::json!([]));
+    }
+
+    #[test]
+    fn counts_cjk_by_character() {
+        let s = text_stats("<p>你好，世界！Hello world, it's well-known.</p>");
+        assert_eq!(s.word_count, 4 + 4);
+        assert_eq!(s.reading_minutes, 1);
+        let s = text_stats("<p>こんにちは 한국어</p>");
+        assert_eq!(s.word_count, 5 + 3);
+    }
+
+    #[test]
+    fn reading_minutes() {
+        assert_eq!(text_stats("").reading_minutes, 0);
+        assert_eq!(text_stats("<p><img src=\"/a.png\"></p>").word_count, 0);
+        let cjk = format!("<p>{}</p>", "字".repeat(601));
+        assert_eq!(text_stats(&cjk).reading_minutes, 3);
+        let words = format!("<p>{}</p>", "word ".repeat(400));
+        assert_eq!(text_stats(&words).reading_minutes, 2);
+        // 两种文字各算各的速度再相加
+        let mixed = format!("<p>{}{}</p>", "字".repeat(300), "word ".repeat(200));
+        assert_eq!(text_stats(&mixed).reading_minutes, 2);
+    }
+
+    #[test]
+    fn excerpt_skips_code_and_headings() {
+        let html = "<h1 id=\"t\">标题</h1><script>var a = 1;</script><pre><code>let x = 1;</code></pre>\
+                    <p>正文 &amp; <em>more</em></p>";
+        let s = text_stats(html);
+        assert_eq!(s.excerpt.as_deref(), Some("正文 & more"));
+        assert_eq!(s.word_count, 2 + 2 + 4); // 标题、正文、代码里的词；脚本不算
+        let long = text_stats(&format!("<p>{}</p>", "长".repeat(200)));
+        let excerpt = long.excerpt.unwrap();
+        assert_eq!(excerpt.chars().count(), EXCERPT_CHARS + 1);
+        assert!(excerpt.ends_with('…'));
+        assert_eq!(text_stats("<pre>code</pre>").excerpt, None);
+    }
 }
@@ -0,0 +1,32 @@
+// 公式：$...$ / $$...$$ / ```math 在服务端转成 MathML，读者不需要 JS，RSS 阅读器里也能显示
+// 解析失败不影响保存：原文按代码样式原样输出，错误信息放在 title 里
+use std::panic::{self, AssertUnwindSafe};
+
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+
+/// TeX → MathML；失败时返回带 math-error 类的原文
+pub fn to_mathml(tex: &str, display: bool) -> String {
+    let style = if display { DisplayStyle::Block } else { DisplayStyle::Inline };
+    // latex2mathml 遇到个别畸形输入会 panic，当作解析错误处理
+    let result = panic::catch_unwind(AssertUnwindSafe(|| latex_to_mathml(tex.trim(), style)));
+    match result {
+        Ok(Ok(mathml)) => mathml,
+        Ok(Err(e)) => error(tex, &e.to_string(), display),
+        Err(_) => error(tex, "invalid formula", display),
+    }
+}
+
+fn error(tex: &str, msg: &str, display: bool) -> String {
+    let (open, close) = if display { ("$$", "$$") } else { ("$", "$") };
+    format!(
+        "<span class=\"math-error\" title=\"{}\"><code>{}{}{}</code></span>",
+        escape(msg),
+        open,
+        escape(tex),
+        close
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,443 @@
+// 上传文件的存储：按内容 SHA-256 命名，放在两级分片目录里（uploads/ab/cd/<hash>.<ext>，存储后端见 storage.rs）
+// 文件名不再来自客户端，../ 之类的名字逃不出 UPLOAD_DIR；内容相同的上传自然去重，URL 也不会变
+use std::io::Cursor;
+
+use image::ImageReader;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::storage::Storage;
+
+// 允许的扩展名（小写）：图片、PDF、音频；别名统一成左边的写法，同样内容的 a.jpeg / b.jpg 落到同一个文件
+const EXTENSIONS: &[&str] =
+    &["jpg", "png", "gif", "webp", "avif", "svg", "pdf", "mp3", "m4a", "ogg", "wav", "flac"];
+const ALIASES: &[(&str, &str)] = &[("jpeg", "jpg"), ("jpe", "jpg"), ("oga", "ogg")];
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("jpg", "image/jpeg"),
+    ("png", "image/png"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("pdf", "application/pdf"),
+    ("mp3", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"),
+];
+
+const MB: u64 = 1024 * 1024;
+const DEFAULT_LIMITS: &str = "image:10MB,pdf:20MB,audio:50MB";
+const DEFAULT_MAX_REQUEST: u64 = 100 * MB;
+
+/// 上传类型（按扩展名）；大小上限按类型配置
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Image,
+    Pdf,
+    Audio,
+}
+
+impl Kind {
+    /// ext 须是 extension 认可的扩展名（白名单里只有图片、音频和 PDF）
+    pub fn of(ext: &str) -> Kind {
+        match mime(ext).split('/').next() {
+            Some("image") => Kind::Image,
+            Some("audio") => Kind::Audio,
+            _ => Kind::Pdf,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Image => "image",
+            Kind::Pdf => "PDF",
+            Kind::Audio => "audio",
+        }
+    }
+}
+
+/// 上传大小上限（字节）
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub image: u64,
+    pub pdf: u64,
+    pub audio: u64,
+    pub request: u64, // 一次 multipart 请求的总大小
+}
+
+impl Limits {
+    /// UPLOAD_LIMITS=image:10MB,pdf:20MB,audio:50MB（只写要改的类型即可）
+    /// UPLOAD_MAX_REQUEST=100MB
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut limits = Self { image: 0, pdf: 0, audio: 0, request: DEFAULT_MAX_REQUEST };
+        let custom = std::env::var("UPLOAD_LIMITS").unwrap_or_default();
+        for item in DEFAULT_LIMITS.split(',').chain(custom.split(',')).map(str::trim) {
+            if item.is_empty() {
+                continue;
+            }
+            let bad = || anyhow::anyhow!("UPLOAD_LIMITS: expected kind:size, got `{}`", item);
+            let (kind, size) = item.split_once(':').ok_or_else(bad)?;
+            let size = parse_size(size).ok_or_else(bad)?;
+            match kind.trim() {
+                "image" => limits.image = size,
+                "pdf" => limits.pdf = size,
+                "audio" => limits.audio = size,
+                k => anyhow::bail!("UPLOAD_LIMITS: unknown kind `{}` (image, pdf, audio)", k),
+            }
+        }
+        if let Ok(v) = std::env::var("UPLOAD_MAX_REQUEST") {
+            limits.request = parse_size(&v)
+                .ok_or_else(|| anyhow::anyhow!("UPLOAD_MAX_REQUEST: invalid size `{}`", v))?;
+        }
+        Ok(limits)
+    }
+
+    pub fn of(&self, kind: Kind) -> u64 {
+        match kind {
+            Kind::Image => self.image,
+            Kind::Pdf => self.pdf,
+            Kind::Audio => self.audio,
+        }
+    }
+}
+
+/// "512KB" / "10MB" / "1GB" / "2048"（字节）
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim().to_ascii_uppercase();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s.as_str(), ""),
+    };
+    let unit = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => MB,
+        "G" | "GB" => 1024 * MB,
+        _ => return None,
+    };
+    num.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// 人看的大小：10 MB / 512 KB / 100 B
+pub fn human_size(n: u64) -> String {
+    match n {
+        n if n >= MB && n % MB == 0 => format!("{} MB", n / MB),
+        n if n >= MB => format!("{:.1} MB", n as f64 / MB as f64),
+        n if n >= 1024 => format!("{} KB", n / 1024),
+        n => format!("{} B", n),
+    }
+}
+
+// 正文里的站内文件地址（含 srcset 缩小版 <hash>.480w.png）→ 内容哈希
+static RE_UPLOAD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"/uploads/[0-9a-f]{2}/[0-9a-f]{2}/([0-9a-f]{64})\.").unwrap()
+});
+
+// 原始文件名只作展示用，截断到这个长度
+const MAX_FILENAME_CHARS: usize = 255;
+
+/// 原始文件名里的扩展名（小写、别名归一）；不在白名单里的报错
+pub fn extension(filename: &str) -> Result<String, String> {
+    let ext = filename
+        .rsplit_once('.')
+        .map(|(_, e)| e.trim().to_ascii_lowercase())
+        .filter(|e| !e.is_empty())
+        .ok_or_else(|| format!("`{}` has no file extension", filename))?;
+    let ext = ALIASES.iter().find(|(a, _)| *a == ext).map_or(ext.as_str(), |(_, e)| e);
+    if EXTENSIONS.contains(&ext) {
+        Ok(ext.to_string())
+    } else {
+        Err(format!("file type `.{}` is not allowed", ext))
+    }
+}
+
+/// 按文件头判断实际类型（返回扩展名）；认不出的返回 None
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    let at = |i: usize, sig: &[u8]| data.get(i..i + sig.len()) == Some(sig);
+    if at(0, b"\xFF\xD8\xFF") {
+        return Some("jpg");
+    }
+    if at(0, b"\x89PNG\r\n\x1A\n") {
+        return Some("png");
+    }
+    if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        return Some("gif");
+    }
+    if at(0, b"RIFF") && at(8, b"WEBP") {
+        return Some("webp");
+    }
+    if at(0, b"RIFF") && at(8, b"WAVE") {
+        return Some("wav");
+    }
+    if at(0, b"%PDF-") {
+        return Some("pdf");
+    }
+    if at(0, b"OggS") {
+        return Some("ogg");
+    }
+    if at(0, b"fLaC") {
+        return Some("flac");
+    }
+    // MP3：ID3 标签开头，或直接是 MPEG 帧同步字
+    if at(0, b"ID3") || (data.len() > 1 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0) {
+        return Some("mp3");
+    }
+    // ISO BMFF：ftyp 盒子里的品牌区分 AVIF 和 M4A
+    if at(4, b"ftyp") {
+        let brand = data.get(8..12)?;
+        return match brand {
+            b"avif" | b"avis" => Some("avif"),
+            b"M4A " | b"M4B " | b"mp42" | b"isom" | b"dash" => Some("m4a"),
+            _ => None,
+        };
+    }
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let head = head.trim_start_matches('\u{FEFF}').trim_start();
+    if (head.starts_with("<?xml") || head.starts_with("<svg") || head.starts_with("<!--"))
+        && head.contains("<svg")
+    {
+        return Some("svg");
+    }
+    None
+}
+
+// SVG 里能执行脚本或嵌入任意内容的元素（按本地名比较，任何命名空间前缀都一样）
+const SVG_ACTIVE_ELEMENTS: &[&str] =
+    &["script", "foreignobject", "iframe", "embed", "object", "handler", "listener"];
+// 动画元素改写这些属性就能把链接换成 javascript:
+const SVG_ANIMATIONS: &[&str] = &["set", "animate", "animatemotion", "animatetransform"];
+
+/// 用 XML 解析器检查 SVG：脚本类元素、on* 事件属性、改写 href 的动画、javascript: 等链接
+/// 属性值是实体解码后的结果；不允许 DTD（<!ENTITY> 之类直接解析失败）
+fn check_svg(text: &str) -> Result<(), String> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| format!("SVG is not well-formed XML: {}", e))?;
+    for node in doc.descendants() {
+        if node.is_pi() {
+            return Err("SVG contains processing instructions".into());
+        }
+        if !node.is_element() {
+            continue;
+        }
+        let name = node.tag_name().name().to_ascii_lowercase();
+        if SVG_ACTIVE_ELEMENTS.contains(&name.as_str()) {
+            return Err(format!("SVG contains a <{}> element", node.tag_name().name()));
+        }
+        for attr in node.attributes() {
+            let attr_name = attr.name().to_ascii_lowercase();
+            if attr_name.starts_with("on") {
+                return Err(format!("SVG contains an event handler ({})", attr.name()));
+            }
+            // 去掉空白和控制字符后再比较协议（浏览器解析 URL 时也会忽略它们）
+            let value: String = attr
+                .value()
+                .chars()
+                .filter(|c| !c.is_whitespace() && !c.is_control())
+                .collect::<String>()
+                .to_ascii_lowercase();
+            if value.contains("javascript:") || value.contains("vbscript:") {
+                return Err("SVG contains a javascript: link".into());
+            }
+            if matches!(attr_name.as_str(), "href" | "src") && value.starts_with("data:") {
+                return Err("SVG links to a data: URL".into());
+            }
+            if SVG_ANIMATIONS.contains(&name.as_str()) && attr_name == "attributename" {
+                let target = value.rsplit(':').next().unwrap_or_default();
+                if target == "href" || target == "src" || target.starts_with("on") {
+                    return Err(format!("SVG animates the {} attribute", attr.value()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 内容是否与扩展名一致；SVG 另外不能带脚本、事件属性、javascript: 链接等
+pub fn check_content(ext: &str, data: &[u8]) -> Result<(), String> {
+    match sniff(data) {
+        Some(actual) if actual == ext => {}
+        Some(actual) => {
+            return Err(format!("content is {} but the file name says .{}", mime(actual), ext));
+        }
+        None => return Err(format!("content is not a valid {} file", mime(ext))),
+    }
+    if ext == "svg" {
+        let text = std::str::from_utf8(data).map_err(|_| "SVG is not valid UTF-8".to_string())?;
+        check_svg(text)?;
+    }
+    Ok(())
+}
+
+/// 扩展名（extension 的结果）对应的 MIME 类型
+pub fn mime(ext: &str) -> &'static str {
+    MIME_TYPES.iter().find(|(e, _)| *e == ext).map_or("application/octet-stream", |(_, m)| m)
+}
+
+/// 位图的宽高（只读文件头）；不是位图的返回 None
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    ImageReader::new(Cursor::new(data)).with_guessed_format().ok()?.into_dimensions().ok()
+}
+
+/// Markdown 正文引用的上传文件（内容哈希，去重）
+pub fn referenced(md: &str) -> Vec<String> {
+    let mut hashes: Vec<String> = vec![];
+    for c in RE_UPLOAD.captures_iter(md) {
+        if !hashes.iter().any(|h| h == &c[1]) {
+            hashes.push(c[1].to_string());
+        }
+    }
+    hashes
+}
+
+/// 客户端给的文件名只保留最后一段（去掉路径和控制字符），用作元数据
+pub fn display_name(filename: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name.chars().filter(|c| !c.is_control()).take(MAX_FILENAME_CHARS).collect();
+    match name.trim() {
+        "" | "." | ".." => "file".into(),
+        n => n.to_string(),
+    }
+}
+
+pub fn hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// <hash>.<ext> 相对 UPLOAD_DIR 的路径：ab/cd/<hash>.<ext>
+pub fn rel_path(hash: &str, ext: &str) -> String {
+    format!("{}/{}/{}.{}", &hash[..2], &hash[2..4], hash, ext)
+}
+
+/// 对外的稳定地址
+pub fn url(hash: &str, ext: &str) -> String {
+    format!("/uploads/{}", rel_path(hash, ext))
+}
+
+/// 写入文件（已存在则跳过）；返回哈希和是否新写入
+pub async fn store(storage: &dyn Storage, ext: &str, data: &[u8]) -> std::io::Result<(String, bool)> {
+    let hash = hash(data);
+    let key = rel_path(&hash, ext);
+    if storage.exists(&key).await? {
+        return Ok((hash, false));
+    }
+    storage.put(&key, data, mime(ext)).await?;
+    Ok((hash, true))
+}
+
+/// 删除文件和派生的缩小版、WebP / AVIF（<hash>.480w.<ext> 等）；文件本来就不在也算成功
+pub async fn remove(storage: &dyn Storage, hash: &str, ext: &str) -> std::io::Result<()> {
+    let key = rel_path(hash, ext);
+    let Some((dir, _)) = key.rsplit_once('/') else { return Ok(()) };
+    for (key, _) in storage.list(&format!("{}/{}.", dir, hash)).await? {
+        storage.delete(&key).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR";
+    const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#;
+
+    fn svg(body: &str) -> Result<(), String> {
+        check_content("svg", format!("{}{}</svg>", SVG, body).as_bytes())
+    }
+
+    #[test]
+    fn sniffs_magic_bytes() {
+        assert_eq!(sniff(b"\xFF\xD8\xFF\xE0"), Some("jpg"));
+        assert_eq!(sniff(PNG), Some("png"));
+        assert_eq!(sniff(b"GIF89a"), Some("gif"));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("webp"));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WAVEfmt "), Some("wav"));
+        assert_eq!(sniff(b"%PDF-1.7"), Some("pdf"));
+        assert_eq!(sniff(b"ID3\x04"), Some("mp3"));
+        assert_eq!(sniff(b"\xEF\xBB\xBF  <?xml version=\"1.0\"?><svg/>"), Some("svg"));
+        assert_eq!(sniff(b"<html><svg/></html>"), None);
+        assert_eq!(sniff(b""), None);
+    }
+
+    #[test]
+    fn sniffs_iso_bmff_brands() {
+        let ftyp = |brand: &[u8]| [b"\0\0\0\x18ftyp".as_slice(), brand].concat();
+        assert_eq!(sniff(&ftyp(b"avif")), Some("avif"));
+        assert_eq!(sniff(&ftyp(b"avis")), Some("avif"));
+        assert_eq!(sniff(&ftyp(b"M4A ")), Some("m4a"));
+        assert_eq!(sniff(&ftyp(b"isom")), Some("m4a"));
+        // 视频、HEIC 之类不收
+        assert_eq!(sniff(&ftyp(b"qt  ")), None);
+        assert_eq!(sniff(&ftyp(b"heic")), None);
+        assert_eq!(sniff(b"\0\0\0\x18ftyp"), None);
+    }
+
+    #[test]
+    fn rejects_mismatched_extension() {
+        assert!(check_content("png", PNG).is_ok());
+        let err = check_content("jpg", PNG).unwrap_err();
+        assert!(err.contains("image/png"), "{}", err);
+        assert!(check_content("svg", PNG).is_err());
+        assert!(check_content("pdf", b"<html>%PDF-</html>").is_err());
+        assert!(check_content("png", b"not an image").is_err());
+    }
+
+    #[test]
+    fn accepts_plain_svg() {
+        let body = r##"<rect id="r" fill="red"/><a href="https://example.com/"><use xlink:href="#r"/></a>"##;
+        assert!(svg(body).is_ok());
+        assert!(svg(r#"<animate attributeName="opacity" from="0" to="1"/>"#).is_ok());
+    }
+
+    #[test]
+    fn rejects_active_svg() {
+        for body in [
+            "<script>alert(1)</script>",
+            "<s:script xmlns:s=\"http://www.w3.org/2000/svg\">alert(1)</s:script>",
+            "<foreignObject><div/></foreignObject>",
+            "<rect onload=\"alert(1)\"/>",
+            "<rect ONCLICK=\"alert(1)\"/>",
+            "<a href=\"javascript:alert(1)\"/>",
+            "<a xlink:href=\"java&#x09;script:alert(1)\"/>",
+            "<a href=\" JAVASCRIPT:alert(1)\"/>",
+            "<image href=\"data:image/svg+xml,&lt;svg/&gt;\"/>",
+            "<set attributeName=\"href\" to=\"javascript:alert(1)\"/>",
+            "<animate attributeName=\"xlink:href\" values=\"https://x;javascript:alert(1)\"/>",
+            "<set attributeName=\"onclick\" to=\"alert(1)\"/>",
+            "<?evil x?>",
+        ] {
+            assert!(svg(body).is_err(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn rejects_svg_dtd() {
+        let doc = format!(
+            "<?xml version=\"1.0\"?><!DOCTYPE svg [<!ENTITY x \"javascript:alert(1)\">]>{}<a href=\"&x;\"/></svg>",
+            SVG
+        );
+        assert!(check_content("svg", doc.as_bytes()).is_err());
+        assert!(check_content("svg", format!("{}<rect>", SVG).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn names_never_become_paths() {
+        let h = hash(b"hello");
+        let rel = rel_path(&h, "png");
+        assert_eq!(rel, format!("{}/{}/{}.png", &h[..2], &h[2..4], h));
+        assert_eq!(crate::storage::key(&rel), Some(rel.as_str()));
+        assert_eq!(url(&h, "png"), format!("/uploads/{}", rel));
+        assert_eq!(referenced(&format!("![a]({}) [b]({})", url(&h, "png"), url(&h, "png"))), [h]);
+
+        assert_eq!(extension("Photo.JPEG").unwrap(), "jpg");
+        for bad in ["../../etc/passwd", "a.png/..", "shell.php", "a.png.exe", "noext", "a.svg\\..\\x"] {
+            assert!(extension(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(display_name("../../etc/passwd"), "passwd");
+        assert_eq!(display_name("C:\\Users\\me\\cat.png"), "cat.png");
+        assert_eq!(display_name("dir/.."), "file");
+        assert_eq!(display_name("a\nb.png"), "ab.png");
+    }
+}
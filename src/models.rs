@@ -27,8 +27,26 @@ pub struct PostInput {
     pub tags: Option<Vec<String>>,  // tag 名称数组
     pub status: Option<String>,     // draft/published
     pub excerpt: Option<String>,
-    // ✅ 新增：public/private
+    // ✅ 新增：public/private/protected/unlisted
     pub visibility: Option<String>,
+    // visibility=protected 时的访问密码（只存 Argon2 哈希）；更新时不传则沿用原密码
+    pub password: Option<String>,
+    // 自定义字段（JSON 对象）；更新时不传则保持不变
+    pub custom_fields: Option<serde_json::Map<String, serde_json::Value>>,
+    // 语言代码（zh / en / zh-Hant…），不传用 DEFAULT_LANG；更新时不传则保持不变
+    pub lang: Option<String>,
+    // 作为哪篇文章（id）的译文；传空串表示解除译文关联；不传则保持不变
+    pub translation_of: Option<String>,
+    // Markdown 渲染选项覆盖，如 {"superscript": true}；传 {} 恢复站点默认；更新时不传则保持不变
+    pub markdown_options: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// POST /api/preview：只渲染不保存
+#[derive(Debug, Deserialize)]
+pub struct RenderPreviewInput {
+    pub body_md: String,
+    // 同 PostInput::markdown_options；不传用站点默认
+    pub markdown_options: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 pub fn new_id() -> String { Uuid::new_v4().to_string() }
@@ -0,0 +1,569 @@
+// 文章写入的公共逻辑：HTTP 接口（routes::admin）与命令行导入（src/bin）共用
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{Map, Value};
+use sqlx::{SqliteConnection, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    auth,
+    config::Config,
+    custom_fields,
+    db::Db,
+    error::AppError,
+    images,
+    media,
+    markdown::{self, LinkTarget, TextStats},
+    models::{new_id, now, PostInput},
+};
+
+/// 由 body_md 派生、随文章一起保存的字段
+pub struct Derived {
+    pub html: String,
+    pub toc_json: String,
+    pub stats: TextStats,
+    pub options_json: String, // 实际生效的渲染选项（render_options 列）
+    pub links: Vec<(String, Option<String>)>, // [[...]] → (目标 slug, 目标文章 id)
+    pub media: Vec<String>, // 正文引用的上传文件（内容哈希）
+    pub warnings: Vec<String>, // 保存 / 预览时提示给作者（失效的链接、缺图、缺替代文字）
+}
+
+/// overrides：文章自己的渲染选项覆盖（markdown_options），叠加在站点默认值上
+/// [[target]] 先按 slug、再按 slugify(target) 查文章；只认已发布的公开 / 不公开列出的文章和 author_id 自己的文章，
+/// 免得预览或失效链接提示泄露别人草稿、私密文章的标题与是否存在
+pub async fn derive(
+    conn: &mut SqliteConnection,
+    md: &str,
+    cfg: &Config,
+    author_id: &str,
+    overrides: Option<&Map<String, Value>>,
+) -> Result<Derived, AppError> {
+    derive_with(conn, md, cfg, author_id, overrides, false).await
+}
+
+/// 编辑器预览：和 derive 相同，但图片只读文件头、只列已有的缩小版，不生成新文件（不写存储）
+pub async fn preview(
+    conn: &mut SqliteConnection,
+    md: &str,
+    cfg: &Config,
+    author_id: &str,
+    overrides: Option<&Map<String, Value>>,
+) -> Result<Derived, AppError> {
+    derive_with(conn, md, cfg, author_id, overrides, true).await
+}
+
+async fn derive_with(
+    conn: &mut SqliteConnection,
+    md: &str,
+    cfg: &Config,
+    author_id: &str,
+    overrides: Option<&Map<String, Value>>,
+    read_only: bool,
+) -> Result<Derived, AppError> {
+    let options = match overrides {
+        Some(o) => cfg.markdown.with(o).map_err(AppError::BadRequest)?,
+        None => cfg.markdown.clone(),
+    };
+
+    let mut found = HashMap::new();
+    let mut slugs = HashMap::new(); // target → (slug, id)
+    for target in markdown::wiki_targets(md, &options, &cfg.shortcodes) {
+        let alt = slugify(&target);
+        let row = sqlx::query!(
+            r#"SELECT id as "id!: String", slug, title FROM posts
+                WHERE (slug = ? OR slug = ?)
+                  AND (author_id = ? OR (status = 'published' AND visibility IN ('public','unlisted')))
+                ORDER BY slug = ? DESC LIMIT 1"#,
+            target,
+            alt,
+            author_id,
+            target
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        match row {
+            Some(r) => {
+                let url = format!("{}/posts/{}", cfg.site_base, r.slug);
+                found.insert(target.clone(), LinkTarget { url, title: r.title });
+                slugs.insert(target, (r.slug, Some(r.id)));
+            }
+            None => {
+                let slug = if alt.is_empty() { target.clone() } else { alt };
+                slugs.insert(target, (slug, None));
+            }
+        }
+    }
+
+    // 读出站内图片的宽高，缺的缩小版顺便补上（预览时不补）
+    let urls = markdown::image_urls(md, &options, &cfg.shortcodes);
+    let images = if read_only {
+        images::peek(&cfg.images, &*cfg.storage, &urls).await
+    } else {
+        images::resolve(&cfg.images, &*cfg.storage, &urls).await
+    };
+
+    let rendered =
+        markdown::render(md, &options, &cfg.shortcodes, &found, &images, &cfg.sanitize);
+    let stats = markdown::text_stats(&rendered.html);
+
+    let mut warnings: Vec<String> =
+        rendered.unresolved.iter().map(|t| format!("unresolved link [[{}]]", t)).collect();
+    // 普通链接里写死的 /posts/<slug>（相对地址或带 SITE_BASE）
+    for href in &rendered.hrefs {
+        let path = href.strip_prefix(cfg.site_base.as_str()).unwrap_or(href);
+        let Some(slug) = path.strip_prefix("/posts/") else { continue };
+        let slug = slug.split(['/', '?', '#']).next().unwrap_or_default();
+        let exists = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) FROM posts WHERE slug = ?
+                AND (author_id = ? OR (status = 'published' AND visibility IN ('public','unlisted')))"#,
+            slug,
+            author_id
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if exists == 0 {
+            warnings.push(format!("broken link {}", href));
+        }
+    }
+    for url in urls.iter().filter(|u| !images.contains_key(*u)) {
+        warnings.push(format!("missing image {}", url));
+    }
+    for url in &rendered.missing_alt {
+        warnings.push(format!("image {} has no alt text", url));
+    }
+
+    Ok(Derived {
+        toc_json: serde_json::json!(rendered.toc).to_string(),
+        stats,
+        options_json: serde_json::json!(options).to_string(),
+        links: rendered.links.iter().filter_map(|t| slugs.remove(t)).collect(),
+        media: media::referenced(md),
+        warnings,
+        html: rendered.html,
+    })
+}
+
+/// 整体重建文章的出链
+pub async fn save_links(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    post_id: &str,
+    links: &[(String, Option<String>)],
+) -> Result<(), AppError> {
+    sqlx::query!("DELETE FROM post_links WHERE source_id = ?", post_id)
+        .execute(&mut **tx)
+        .await?;
+    for (slug, target_id) in links {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO post_links (source_id, target_slug, target_id) VALUES (?,?,?)",
+            post_id,
+            slug,
+            target_id
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+/// 整体重建文章引用的上传文件
+pub async fn save_media(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    post_id: &str,
+    hashes: &[String],
+) -> Result<(), AppError> {
+    sqlx::query!("DELETE FROM post_media WHERE post_id = ?", post_id)
+        .execute(&mut **tx)
+        .await?;
+    for hash in hashes {
+        sqlx::query!("INSERT OR IGNORE INTO post_media (post_id, hash) VALUES (?,?)", post_id, hash)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// 文章已保存的渲染选项覆盖（更新时没传 markdown_options 就沿用）
+pub async fn stored_overrides(db: &Db, post_id: &str) -> Result<Option<Map<String, Value>>, AppError> {
+    let raw = sqlx::query_scalar!("SELECT markdown_options FROM posts WHERE id = ?", post_id)
+        .fetch_optional(db)
+        .await?
+        .flatten();
+    Ok(raw.and_then(|s| serde_json::from_str(&s).ok()))
+}
+
+/// 请求里的覆盖 → markdown_options 列（None 表示不改）
+pub fn overrides_json(inp: &PostInput) -> Option<String> {
+    inp.markdown_options.as_ref().map(|m| Value::Object(m.clone()).to_string())
+}
+
+/// 新建文章的时间：接口里都是“现在”，导入时沿用原文的日期
+pub struct Timestamps {
+    pub created_at: String,
+    pub updated_at: String,
+    pub published_at: Option<String>,
+}
+
+impl Timestamps {
+    pub fn now() -> Self {
+        let ts = now();
+        Self { created_at: ts.clone(), updated_at: ts, published_at: None }
+    }
+}
+
+/// 校验并插入一篇新文章（含标签、译文关联与出链），返回 (id, slug, 提示)
+pub async fn insert_post(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    cfg: &Config,
+    author_id: &str,
+    inp: &PostInput,
+    ts: &Timestamps,
+) -> Result<(String, String, Vec<String>), AppError> {
+    let id = new_id();
+    let slug = inp.slug.clone().unwrap_or_else(|| slugify(&inp.title));
+    let d = derive(tx, &inp.body_md, cfg, author_id, inp.markdown_options.as_ref()).await?;
+    let overrides_json = overrides_json(inp);
+    let render_version = markdown::RENDERER_VERSION;
+    let status = inp.status.clone().unwrap_or_else(|| "draft".into());
+    let visibility = inp
+        .visibility
+        .clone()
+        .unwrap_or_else(|| "public".into());
+    check_visibility(&visibility)?;
+    let password_hash = match (visibility.as_str(), inp.password.as_deref()) {
+        ("protected", Some(pw)) if !pw.is_empty() => Some(auth::hash_password(pw)?),
+        ("protected", _) => {
+            return Err(AppError::BadRequest("protected post requires a password".into()))
+        }
+        _ => None,
+    };
+    let fields = inp.custom_fields.clone().unwrap_or_default();
+    custom_fields::validate(&fields, cfg.custom_fields.as_ref())?;
+    let fields_json = serde_json::Value::Object(fields).to_string();
+    let lang = inp.lang.clone().unwrap_or_else(|| cfg.default_lang.clone());
+    check_lang(&lang)?;
+
+    let group = match inp.translation_of.as_deref() {
+        Some(target) if !target.is_empty() => {
+            Some(translation_group_for(tx, cfg, &id, author_id, target, &lang).await?)
+        }
+        _ => None,
+    };
+
+    sqlx::query!(
+        r#"INSERT INTO posts (
+            id, slug, title, excerpt, body_md, body_html, toc, status, visibility, password_hash,
+            custom_fields, word_count, reading_minutes, auto_excerpt, lang, translation_group,
+            markdown_options, render_options, render_version, author_id, published_at, created_at,
+            updated_at
+          ) VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)"#,
+        id,
+        slug,
+        inp.title,
+        inp.excerpt,
+        inp.body_md,
+        d.html,
+        d.toc_json,
+        status,
+        visibility,
+        password_hash,
+        fields_json,
+        d.stats.word_count,
+        d.stats.reading_minutes,
+        d.stats.excerpt,
+        lang,
+        group,
+        overrides_json,
+        d.options_json,
+        render_version,
+        author_id,
+        ts.published_at,
+        ts.created_at,
+        ts.updated_at
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    if let Some(group) = &group {
+        join_translation_group(tx, group).await?;
+    }
+    if let Some(tags) = &inp.tags {
+        add_tags(tx, &id, tags).await?;
+    }
+    save_links(tx, &id, &d.links).await?;
+    save_media(tx, &id, &d.media).await?;
+
+    Ok((id, slug, d.warnings))
+}
+
+pub async fn add_tags(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    post_id: &str,
+    tags: &[String],
+) -> Result<(), AppError> {
+    for name in tags {
+        let tag_id = ensure_tag(tx, name).await?;
+        sqlx::query!(
+            "INSERT OR IGNORE INTO post_tags (post_id, tag_id) VALUES (?,?)",
+            post_id,
+            tag_id
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+/// 整体重建文章的标签关联（简单粗暴，但清晰可靠）
+pub async fn set_tags(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    post_id: &str,
+    tags: &[String],
+) -> Result<(), AppError> {
+    sqlx::query!("DELETE FROM post_tags WHERE post_id = ?", post_id)
+        .execute(&mut **tx)
+        .await?;
+    add_tags(tx, post_id, tags).await
+}
+
+pub fn check_visibility(v: &str) -> Result<(), AppError> {
+    match v {
+        "public" | "private" | "protected" | "unlisted" => Ok(()),
+        _ => Err(AppError::BadRequest(format!("invalid visibility: {}", v))),
+    }
+}
+
+static RE_LANG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[A-Za-z]{2,3}(-[A-Za-z0-9]{2,8})*$").unwrap());
+
+/// 语言代码：zh / en / zh-Hant / pt-BR 之类
+pub fn check_lang(lang: &str) -> Result<(), AppError> {
+    if RE_LANG.is_match(lang) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!("invalid lang: {}", lang)))
+    }
+}
+
+/// translation_of → 译文组 id（对方已有组则沿用，否则以对方 id 作为组 id）
+/// 只能关联自己的文章（管理员不限），否则会改动别人文章的译文组
+pub async fn translation_group_for(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    cfg: &Config,
+    post_id: &str,
+    author_id: &str,
+    target_id: &str,
+    lang: &str,
+) -> Result<String, AppError> {
+    if target_id == post_id {
+        return Err(AppError::BadRequest("a post cannot be its own translation".into()));
+    }
+    let target = sqlx::query!(
+        r#"SELECT id as "id!: String", translation_group, author_id FROM posts WHERE id = ?"#,
+        target_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| AppError::BadRequest(format!("translation_of: post {} not found", target_id)))?;
+    if target.author_id != author_id {
+        let name = sqlx::query_scalar!("SELECT username FROM users WHERE id = ?", author_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+        if !name.is_some_and(|n| cfg.admin_users.contains(&n)) {
+            return Err(AppError::Forbidden);
+        }
+    }
+
+    let group = target.translation_group.unwrap_or(target.id);
+    ensure_lang_free(tx, &group, lang, post_id).await?;
+    Ok(group)
+}
+
+/// 同一译文组内每种语言只能有一篇
+pub async fn ensure_lang_free(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    group: &str,
+    lang: &str,
+    post_id: &str,
+) -> Result<(), AppError> {
+    let taken = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "n!: i64" FROM posts
+            WHERE (translation_group = ? OR id = ?) AND lang = ? AND id != ?"#,
+        group,
+        group,
+        lang,
+        post_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    if taken > 0 {
+        return Err(AppError::BadRequest(format!(
+            "translation group already has a `{}` post",
+            lang
+        )));
+    }
+    Ok(())
+}
+
+/// 组 id 即首篇文章 id：确保首篇自己也标记了 translation_group
+pub async fn join_translation_group(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    group: &str,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE posts SET translation_group = ? WHERE id = ? AND translation_group IS NULL",
+        group,
+        group
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub fn slugify(title: &str) -> String {
+    let s = title.trim().to_lowercase();
+    let re = Regex::new(r"[^a-z0-9]+").unwrap();
+    let s = re.replace_all(&s, "-").to_string();
+    s.trim_matches('-').to_string()
+}
+
+pub async fn ensure_tag(
+    tx: &mut Transaction<'_, sqlx::Sqlite>,
+    name: &str,
+) -> Result<String, AppError> {
+    let slug = slugify(name);
+    let row = sqlx::query!(
+        r#"SELECT id as "id!: String" FROM tags WHERE slug = ?"#,
+        slug
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if let Some(row) = row {
+        return Ok(row.id);
+    }
+
+    let id = Uuid::new_v4().to_string();
+    sqlx::query!(
+        "INSERT INTO tags (id, slug, name) VALUES (?,?,?)",
+        id,
+        slug,
+        name
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn lang_codes() {
+        for ok in ["zh", "en", "zh-Hant", "pt-BR", "yue", "sr-Latn-RS"] {
+            assert!(check_lang(ok).is_ok(), "{}", ok);
+        }
+        for bad in ["", "e", "english", "zh_CN", "zh-", "-en", "en-toolongsub", "en US", "../x"] {
+            assert!(check_lang(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn translation_group_owner() {
+        let db = testing::db().await;
+        let cfg = testing::config();
+        let alice = testing::user(&db, "alice").await;
+        let bob = testing::user(&db, "bob").await;
+        let admin = testing::user(&db, "admin").await;
+        let post = |author: String, slug: &'static str, lang: &'static str, of: String| {
+            let body = serde_json::json!({
+                "title": slug, "slug": slug, "body_md": "x", "lang": lang, "translation_of": of
+            });
+            let (db, cfg) = (db.clone(), cfg.clone());
+            async move { testing::post(&db, &cfg, &author, body).await }
+        };
+        let zh = post(alice.clone(), "zh", "zh", String::new()).await.unwrap();
+
+        // 别人的文章不能拿来当原文，管理员可以
+        assert!(matches!(post(bob.clone(), "bob-en", "en", zh.clone()).await, Err(AppError::Forbidden)));
+        let en = post(alice.clone(), "en", "en", zh.clone()).await.unwrap();
+        post(admin.clone(), "ja", "ja", en.clone()).await.unwrap();
+
+        let groups: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT slug, translation_group FROM posts ORDER BY slug")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+        assert!(groups.iter().all(|(_, g)| g.as_deref() == Some(zh.as_str())), "{:?}", groups);
+
+        // 同组同语言只能一篇；不能是自己的译文；原文得存在
+        assert!(matches!(post(alice.clone(), "en-2", "en", zh.clone()).await, Err(AppError::BadRequest(_))));
+        assert!(matches!(post(alice.clone(), "de", "de", "missing".into()).await, Err(AppError::BadRequest(_))));
+        let mut tx = db.begin().await.unwrap();
+        let r = translation_group_for(&mut tx, &cfg, &zh, &alice, &zh, "de").await;
+        assert!(matches!(r, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn wiki_links_and_backlinks() {
+        let db = testing::db().await;
+        let cfg = testing::config();
+        let alice = testing::user(&db, "alice").await;
+        let bob = testing::user(&db, "bob").await;
+        let mut ids = HashMap::new();
+        for (author, slug, status, visibility) in [
+            (&bob, "bob-pub", "published", "public"),
+            (&bob, "bob-unlisted", "published", "unlisted"),
+            (&bob, "bob-private", "published", "private"),
+            (&bob, "bob-draft", "draft", "public"),
+            (&alice, "alice-draft", "draft", "public"),
+        ] {
+            let title = format!("Title of {}", slug);
+            let body = serde_json::json!({
+                "title": title, "slug": slug, "body_md": "x", "status": status, "visibility": visibility
+            });
+            ids.insert(slug, testing::post(&db, &cfg, author, body).await.unwrap());
+        }
+
+        let md = "[[bob-pub]] [[Bob Unlisted]] [[bob-private]] [[bob-draft]] [[alice-draft]] [[later]]";
+        let d = derive(&mut db.acquire().await.unwrap(), md, &cfg, &alice, None).await.unwrap();
+        let links: HashMap<_, _> = d.links.iter().cloned().collect();
+        assert_eq!(links["bob-pub"].as_deref(), Some(ids["bob-pub"].as_str()));
+        // 按 slugify 后的 slug 也能找到
+        assert_eq!(links["bob-unlisted"].as_deref(), Some(ids["bob-unlisted"].as_str()));
+        // 作者自己的草稿可以链；别人的私密文章、草稿当作不存在，不泄露标题
+        assert_eq!(links["alice-draft"].as_deref(), Some(ids["alice-draft"].as_str()));
+        assert_eq!(links["bob-private"], None);
+        assert_eq!(links["bob-draft"], None);
+        assert_eq!(links["later"], None);
+        assert!(d.html.contains("Title of bob-pub"), "{}", d.html);
+        assert!(!d.html.contains("Title of bob-private") && !d.html.contains("Title of bob-draft"));
+        let unresolved: Vec<_> = d.warnings.iter().filter(|w| w.starts_with("unresolved link")).collect();
+        assert_eq!(unresolved.len(), 3, "{:?}", d.warnings);
+
+        // 反向链接：只列已发布的 public 文章；先链后建（target_id 为空、按 slug）的也算
+        for (slug, status, visibility) in [
+            ("linker", "published", "public"),
+            ("linker-draft", "draft", "public"),
+            ("linker-unlisted", "published", "unlisted"),
+        ] {
+            let body = serde_json::json!({
+                "title": slug, "slug": slug, "body_md": "[[bob-pub]] [[later]]", "status": status,
+                "visibility": visibility
+            });
+            testing::post(&db, &cfg, &alice, body).await.unwrap();
+        }
+        let body = serde_json::json!({ "title": "later", "slug": "later", "body_md": "x" });
+        testing::post(&db, &cfg, &bob, body).await.unwrap();
+        for target in ["bob-pub", "later"] {
+            let p = crate::routes::public::load_post(&db, target, None, false).await.unwrap().unwrap();
+            let back = crate::routes::public::backlinks(&db, &p).await.unwrap();
+            let slugs: Vec<_> = back.iter().map(|b| b["slug"].as_str().unwrap()).collect();
+            assert_eq!(slugs, ["linker"], "{}", target);
+        }
+    }
+}
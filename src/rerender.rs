@@ -0,0 +1,170 @@
+// 批量重新渲染：markdown 管线升级后，把 render_version 落后的文章（或全部）按批重算 body_html 等派生字段
+// 按各文章上次实际生效的渲染选项渲染；不改 updated_at，订阅与站点地图不受影响
+// 命令行（src/bin/rerender.rs）与管理接口（/api/rerender，后台任务）共用
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{config::Config, db::Db, markdown::RENDERER_VERSION, posts};
+
+// 失败明细最多保留的条数
+const MAX_ERRORS: usize = 50;
+
+#[derive(Serialize, Clone, Default)]
+pub struct Progress {
+    pub total: i64,
+    pub done: i64,
+    pub failed: i64,
+    pub errors: Vec<String>, // "slug: 原因"
+}
+
+/// 后台任务状态（AppState 里共享）
+#[derive(Serialize, Clone, Default)]
+pub struct Job {
+    pub running: bool,
+    pub all: bool,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub progress: Progress,
+}
+
+/// 待重新渲染的文章数；all 为 true 时是全部文章
+pub async fn pending(db: &Db, all: bool) -> anyhow::Result<i64> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "n!: i64" FROM posts WHERE ? OR render_version < ?"#,
+        all,
+        RENDERER_VERSION
+    )
+    .fetch_one(db)
+    .await?)
+}
+
+/// 每处理完一批调用一次 on_batch；单篇失败只记录，不中断
+pub async fn run(
+    db: &Db,
+    cfg: &Config,
+    all: bool,
+    batch: i64,
+    mut on_batch: impl FnMut(&Progress),
+) -> anyhow::Result<Progress> {
+    let mut progress = Progress { total: pending(db, all).await?, ..Default::default() };
+    on_batch(&progress);
+
+    // 按 id 游标推进：失败的文章版本号不变，不会被反复取到
+    let mut cursor = String::new();
+    loop {
+        let rows = sqlx::query!(
+            r#"SELECT id as "id!: String", slug, body_md, markdown_options, render_options, author_id
+                 FROM posts WHERE id > ? AND (? OR render_version < ?)
+                ORDER BY id LIMIT ?"#,
+            cursor,
+            all,
+            RENDERER_VERSION,
+            batch
+        )
+        .fetch_all(db)
+        .await?;
+        let Some(last) = rows.last() else { break };
+        cursor = last.id.clone();
+
+        for r in &rows {
+            let options = recorded_options(cfg, r.render_options.as_deref(), r.markdown_options.as_deref());
+            let source = (r.body_md.as_str(), r.markdown_options.as_deref());
+            match rerender_post(db, cfg, &r.id, &r.author_id, source, &options).await {
+                Ok(()) => progress.done += 1,
+                Err(e) => {
+                    progress.failed += 1;
+                    if progress.errors.len() < MAX_ERRORS {
+                        progress.errors.push(format!("{}: {}", r.slug, e));
+                    }
+                }
+            }
+        }
+        on_batch(&progress);
+    }
+    Ok(progress)
+}
+
+/// 上次生效的完整选项（去掉本版本已不认识的键）；没有记录时退回文章自己的覆盖
+fn recorded_options(cfg: &Config, render_options: Option<&str>, overrides: Option<&str>) -> Map<String, Value> {
+    let known = match serde_json::to_value(&cfg.markdown) {
+        Ok(Value::Object(m)) => m,
+        _ => Map::new(),
+    };
+    let parse = |s: Option<&str>| s.and_then(|s| serde_json::from_str::<Map<String, Value>>(s).ok());
+    parse(render_options)
+        .or_else(|| parse(overrides))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(k, _)| known.contains_key(k))
+        .collect()
+}
+
+/// 文章改了 slug、状态或可见性后，重新渲染链到它的文章（[[...]] 的地址、标题和能否解析都跟着变）
+/// 包括之前按这个 slug 没解析到的链接；单篇失败只记日志
+pub async fn linking(db: &Db, cfg: &Config, post_id: &str, slug: &str) -> anyhow::Result<()> {
+    let rows = sqlx::query!(
+        r#"SELECT DISTINCT p.id as "id!: String", p.slug, p.body_md, p.markdown_options, p.render_options, p.author_id
+             FROM post_links l JOIN posts p ON p.id = l.source_id
+            WHERE (l.target_id = ? OR (l.target_id IS NULL AND l.target_slug = ?)) AND p.id != ?"#,
+        post_id,
+        slug,
+        post_id
+    )
+    .fetch_all(db)
+    .await?;
+    for r in &rows {
+        let options = recorded_options(cfg, r.render_options.as_deref(), r.markdown_options.as_deref());
+        let source = (r.body_md.as_str(), r.markdown_options.as_deref());
+        if let Err(e) = rerender_post(db, cfg, &r.id, &r.author_id, source, &options).await {
+            tracing::warn!("rerender {}: {:#}", r.slug, e);
+        }
+    }
+    Ok(())
+}
+
+/// source 是读出来的 (body_md, markdown_options)：只有库里仍是这份内容时才写回；
+/// 期间作者保存过的话，保存时已按新内容渲染，这里放弃，免得新正文配上旧内容渲染的结果
+async fn rerender_post(
+    db: &Db,
+    cfg: &Config,
+    id: &str,
+    author_id: &str,
+    (body_md, markdown_options): (&str, Option<&str>),
+    options: &Map<String, Value>,
+) -> anyhow::Result<()> {
+    let mut tx = db.begin().await?;
+    let d = posts::derive(&mut tx, body_md, cfg, author_id, Some(options)).await?;
+    let updated = sqlx::query!(
+        r#"
+        UPDATE posts SET
+            body_html       = ?,
+            toc             = ?,
+            word_count      = ?,
+            reading_minutes = ?,
+            auto_excerpt    = ?,
+            render_options  = ?,
+            render_version  = ?
+        WHERE id = ? AND body_md = ? AND markdown_options IS ?
+        "#,
+        d.html,
+        d.toc_json,
+        d.stats.word_count,
+        d.stats.reading_minutes,
+        d.stats.excerpt,
+        d.options_json,
+        RENDERER_VERSION,
+        id,
+        body_md,
+        markdown_options
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(());
+    }
+    posts::save_links(&mut tx, id, &d.links).await?;
+    posts::save_media(&mut tx, id, &d.media).await?;
+    tx.commit().await?;
+    Ok(())
+}
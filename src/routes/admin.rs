@@ -1,20 +1,49 @@
 use crate::{
     auth::{self, AuthUser},
+    backup,
+    custom_fields,
     error::AppError,
+    exporter,
+    images,
     markdown,
-    models::{new_id, now, PostInput},
+    media,
+    models::{new_id, now, PostInput, RenderPreviewInput},
+    posts,
+    rerender,
     state::AppState,
 };
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use rand::{distributions::Alphanumeric, Rng};
 use axum::{
-    extract::{Multipart, Path, State},
+    body::Body,
+    extract::{
+        multipart::{Field, MultipartError},
+        Multipart, Path, Query, State,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     Json,
 };
-use regex::Regex;
+use bytes::BytesMut;
 use serde::Deserialize;
-use sqlx::{self, Transaction};
-use std::{fs, path::PathBuf};
-use uuid::Uuid;
+use sqlx::{sqlite::SqliteRow, QueryBuilder, Row, Sqlite};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream},
+    runtime::Handle,
+};
+
+// 后台重新渲染每批篇数
+const RERENDER_BATCH: i64 = 100;
+
+// 导出 / 备份边生成边下载时的缓冲大小
+const STREAM_CHUNK: usize = 64 * 1024;
+
+// 预览链接默认 / 最长有效期（小时）
+const PREVIEW_DEFAULT_HOURS: i64 = 72;
+const PREVIEW_MAX_HOURS: i64 = 24 * 30;
 
 #[derive(Deserialize)]
 pub struct LoginInput {
@@ -53,51 +82,36 @@ pub async fn create_post(
     AuthUser { user_id }: AuthUser,
     Json(inp): Json<PostInput>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let id = new_id();
-    let slug = inp.slug.clone().unwrap_or_else(|| slugify(&inp.title));
-    let html = markdown::render(&inp.body_md);
-    let now_ts = now();
-    let status = inp.status.clone().unwrap_or_else(|| "draft".into());
-    let visibility = inp
-        .visibility
-        .clone()
-        .unwrap_or_else(|| "public".into());
-
     let mut tx = app.db.begin().await?;
-    sqlx::query!(
-        r#"INSERT INTO posts (
-            id, slug, title, excerpt, body_md, body_html, status, visibility, author_id, created_at, updated_at
-          ) VALUES (?,?,?,?,?,?,?,?,?,?,?)"#,
-        id,
-        slug,
-        inp.title,
-        inp.excerpt,
-        inp.body_md,
-        html,
-        status,
-        visibility,
-        user_id,
-        now_ts,
-        now_ts
+    let (id, slug, warnings) =
+        posts::insert_post(&mut tx, &app.cfg, &user_id, &inp, &posts::Timestamps::now()).await?;
+    tx.commit().await?;
+    Ok(Json(serde_json::json!({ "id": id, "slug": slug, "warnings": warnings })))
+}
+
+/// POST /api/preview —— 编辑器实时预览：走和保存相同的渲染管线，不写库也不写存储（不生成图片缩小版）
+pub async fn render_preview(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Json(inp): Json<RenderPreviewInput>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let d = posts::preview(
+        &mut *app.db.acquire().await?,
+        &inp.body_md,
+        &app.cfg,
+        &user_id,
+        inp.markdown_options.as_ref(),
     )
-    .execute(&mut *tx)
     .await?;
-
-    if let Some(tags) = &inp.tags {
-        for name in tags {
-            let tag_id = ensure_tag(&mut tx, name).await?;
-            sqlx::query!(
-                "INSERT OR IGNORE INTO post_tags (post_id, tag_id) VALUES (?,?)",
-                id,
-                tag_id
-            )
-            .execute(&mut *tx)
-            .await?;
-        }
-    }
-
-    tx.commit().await?;
-    Ok(Json(serde_json::json!({ "id": id, "slug": slug })))
+    Ok(Json(serde_json::json!({
+        "html": d.html,
+        "toc": markdown::parse_toc(&d.toc_json),
+        "word_count": d.stats.word_count,
+        "reading_minutes": d.stats.reading_minutes,
+        "excerpt": d.stats.excerpt,
+        "render_options": serde_json::from_str::<serde_json::Value>(&d.options_json).ok(),
+        "warnings": d.warnings,
+    })))
 }
 
 /// PUT /api/posts/:id
@@ -107,67 +121,165 @@ pub async fn update_post(
     Path(id): Path<String>,
     Json(inp): Json<PostInput>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let slug = inp.slug.clone().unwrap_or_else(|| slugify(&inp.title));
-    let html = markdown::render(&inp.body_md);
+    let slug = inp.slug.clone().unwrap_or_else(|| posts::slugify(&inp.title));
+    let overrides = match &inp.markdown_options {
+        Some(o) => Some(o.clone()),
+        None => posts::stored_overrides(&app.db, &id).await?,
+    };
+    let d = posts::derive(
+        &mut *app.db.acquire().await?,
+        &inp.body_md,
+        &app.cfg,
+        &user_id,
+        overrides.as_ref(),
+    )
+    .await?;
+    let render_version = markdown::RENDERER_VERSION;
+    let overrides_json = posts::overrides_json(&inp);
     let ts = now();
     let status = inp.status.clone().unwrap_or_else(|| "draft".into());
     let visibility = inp
         .visibility
         .clone()
         .unwrap_or_else(|| "public".into());
+    posts::check_visibility(&visibility)?;
+    // protected：传了新密码则替换，否则沿用原密码（原来没有密码则报错）；其它可见性清空密码
+    let password_hash = match inp.password.as_deref() {
+        Some(pw) if visibility == "protected" && !pw.is_empty() => Some(auth::hash_password(pw)?),
+        _ => None,
+    };
+    if visibility == "protected" && password_hash.is_none() {
+        let has_password = sqlx::query_scalar!(
+            "SELECT password_hash IS NOT NULL FROM posts WHERE id = ? AND author_id = ?",
+            id,
+            user_id
+        )
+        .fetch_optional(&app.db)
+        .await?
+        .ok_or(AppError::Forbidden)?;
+        if has_password == 0 {
+            return Err(AppError::BadRequest("protected post requires a password".into()));
+        }
+    }
+    if let Some(lang) = &inp.lang {
+        posts::check_lang(lang)?;
+    }
+    // 未传 custom_fields 时保持原值（COALESCE(NULL, custom_fields)）
+    let fields_json = match &inp.custom_fields {
+        Some(fields) => {
+            custom_fields::validate(fields, app.cfg.custom_fields.as_ref())?;
+            Some(serde_json::Value::Object(fields.clone()).to_string())
+        }
+        None => None,
+    };
 
     // 只允许作者本人更新
+    let mut tx = app.db.begin().await?;
+    let before = sqlx::query!(
+        "SELECT slug, status, visibility FROM posts WHERE id = ? AND author_id = ?",
+        id,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::Forbidden)?;
     let res = sqlx::query!(
         r#"
         UPDATE posts SET
-            slug        = ?,
-            title       = ?,
-            excerpt     = ?,
-            body_md     = ?,
-            body_html   = ?,
-            status      = ?,
-            visibility  = ?,
-            updated_at  = ?
+            slug            = ?,
+            title           = ?,
+            excerpt         = ?,
+            body_md         = ?,
+            body_html       = ?,
+            toc             = ?,
+            status          = ?,
+            visibility      = ?,
+            password_hash   = CASE WHEN ? = 'protected' THEN COALESCE(?, password_hash) END,
+            custom_fields   = COALESCE(?, custom_fields),
+            word_count      = ?,
+            reading_minutes = ?,
+            auto_excerpt    = ?,
+            lang            = COALESCE(?, lang),
+            markdown_options = COALESCE(?, markdown_options),
+            render_options  = ?,
+            render_version  = ?,
+            updated_at      = ?
         WHERE id = ? AND author_id = ?
         "#,
         slug,
         inp.title,
         inp.excerpt,
         inp.body_md,
-        html,
+        d.html,
+        d.toc_json,
         status,
         visibility,
+        // SET 右边的列读到的是旧值，这里按新的可见性判断：改成非 protected 时清掉密码
+        visibility,
+        password_hash,
+        fields_json,
+        d.stats.word_count,
+        d.stats.reading_minutes,
+        d.stats.excerpt,
+        inp.lang,
+        overrides_json,
+        d.options_json,
+        render_version,
         ts,
         id,
         user_id
     )
-    .execute(&app.db)
+    .execute(&mut *tx)
     .await?;
 
     if res.rows_affected() == 0 {
         return Err(AppError::Forbidden);
     }
 
-    // 若传了 tags，则整体重建关联（简单粗暴，但清晰可靠）
+    // 译文关联：传空串解除；传 id 加入对方的译文组；改了语言时检查组内是否重复
+    match inp.translation_of.as_deref() {
+        Some("") => {
+            sqlx::query!("UPDATE posts SET translation_group = NULL WHERE id = ?", id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        Some(target) => {
+            let lang = sqlx::query_scalar!(r#"SELECT lang as "lang!: String" FROM posts WHERE id = ?"#, id)
+                .fetch_one(&mut *tx)
+                .await?;
+            let group = posts::translation_group_for(&mut tx, &app.cfg, &id, &user_id, target, &lang).await?;
+            sqlx::query!("UPDATE posts SET translation_group = ? WHERE id = ?", group, id)
+                .execute(&mut *tx)
+                .await?;
+            posts::join_translation_group(&mut tx, &group).await?;
+        }
+        None if inp.lang.is_some() => {
+            let r = sqlx::query!("SELECT lang, translation_group FROM posts WHERE id = ?", id)
+                .fetch_one(&mut *tx)
+                .await?;
+            if let Some(group) = r.translation_group {
+                posts::ensure_lang_free(&mut tx, &group, &r.lang, &id).await?;
+            }
+        }
+        None => {}
+    }
+    posts::save_links(&mut tx, &id, &d.links).await?;
+    posts::save_media(&mut tx, &id, &d.media).await?;
+    tx.commit().await?;
+
+    // 地址或能否被别人链接变了：链到这篇的文章跟着重新渲染
+    if before.slug != slug || before.status != status || before.visibility != visibility {
+        rerender::linking(&app.db, &app.cfg, &id, &slug).await?;
+    }
+
+    // 若传了 tags，则整体重建关联
     if let Some(tags) = &inp.tags {
         let mut tx = app.db.begin().await?;
-        sqlx::query!("DELETE FROM post_tags WHERE post_id = ?", id)
-            .execute(&mut *tx)
-            .await?;
-        for name in tags {
-            let tag_id = ensure_tag(&mut tx, name).await?;
-            sqlx::query!(
-                "INSERT OR IGNORE INTO post_tags (post_id, tag_id) VALUES (?,?)",
-                id,
-                tag_id
-            )
-            .execute(&mut *tx)
-            .await?;
-        }
+        posts::set_tags(&mut tx, &id, tags).await?;
         tx.commit().await?;
     }
 
-    Ok(Json(serde_json::json!({ "ok": true })))
+    Ok(Json(serde_json::json!({ "ok": true, "warnings": d.warnings })))
 }
 
 /// POST /api/posts/:id/publish
@@ -208,76 +320,467 @@ pub async fn delete_post(
     Ok(Json(serde_json::json!({ "ok": true })))
 }
 
-/// POST /api/media
+#[derive(Deserialize)]
+pub struct PreviewInput {
+    pub ttl_hours: Option<i64>,
+}
+
+/// POST /api/posts/:id/previews —— 为（草稿）文章生成可过期的秘密预览链接
+pub async fn create_preview(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Path(id): Path<String>,
+    Json(inp): Json<PreviewInput>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let post = sqlx::query!(
+        r#"SELECT slug as "slug!: String" FROM posts WHERE id = ? AND author_id = ?"#,
+        id,
+        user_id
+    )
+    .fetch_optional(&app.db)
+    .await?
+    .ok_or(AppError::Forbidden)?;
+
+    let hours = inp
+        .ttl_hours
+        .unwrap_or(PREVIEW_DEFAULT_HOURS)
+        .clamp(1, PREVIEW_MAX_HOURS);
+    let preview_id = new_id();
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    let token_hash = auth::token_hash(&token);
+    let created_at = now();
+    let expires_at = (chrono::Utc::now() + chrono::Duration::hours(hours)).to_rfc3339();
+
+    // 只存摘要，token 只在这里返回一次
+    sqlx::query!(
+        "INSERT INTO preview_tokens (id, token_hash, post_id, created_by, created_at, expires_at)
+         VALUES (?,?,?,?,?,?)",
+        preview_id,
+        token_hash,
+        id,
+        user_id,
+        created_at,
+        expires_at
+    )
+    .execute(&app.db)
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "id": preview_id,
+        "token": token,
+        "url": format!("{}/posts/{}?preview={}", app.cfg.site_base, post.slug, token),
+        "expires_at": expires_at
+    })))
+}
+
+/// GET /api/posts/:id/previews —— 列出文章的预览链接（含已过期 / 已撤销；不含 token，库里只有摘要）
+pub async fn list_previews(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<serde_json::Value>>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT pt.id as "id!: String", pt.created_at, pt.expires_at, pt.revoked_at
+          FROM preview_tokens pt
+          JOIN posts p ON p.id = pt.post_id
+         WHERE pt.post_id = ? AND p.author_id = ?
+         ORDER BY pt.created_at DESC
+        "#,
+        id,
+        user_id
+    )
+    .fetch_all(&app.db)
+    .await?;
+
+    let ts = now();
+    Ok(Json(
+        rows.into_iter()
+            .map(|r| {
+                let active = r.revoked_at.is_none() && r.expires_at > ts;
+                serde_json::json!({
+                    "id": r.id,
+                    "created_at": r.created_at,
+                    "expires_at": r.expires_at,
+                    "revoked_at": r.revoked_at,
+                    "active": active
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// DELETE /api/posts/:id/previews/:preview_id —— 撤销预览链接
+pub async fn revoke_preview(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Path((id, preview_id)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let ts = now();
+    let res = sqlx::query!(
+        r#"
+        UPDATE preview_tokens SET revoked_at = COALESCE(revoked_at, ?)
+         WHERE id = ? AND post_id = ?
+           AND post_id IN (SELECT id FROM posts WHERE author_id = ?)
+        "#,
+        ts,
+        preview_id,
+        id,
+        user_id
+    )
+    .execute(&app.db)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(AppError::Forbidden);
+    }
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// POST /api/media —— 文件按内容哈希存放（见 media.rs），同一用户重复上传返回原来那条记录
+/// 每个文件单独校验（类型白名单、内容与扩展名一致、大小上限）；不合格的记入 errors，其余照常保存，
+/// 全部不合格时返回 400
 pub async fn upload_media(
     State(app): State<AppState>,
-    AuthUser { .. }: AuthUser,
+    AuthUser { user_id }: AuthUser,
     mut mp: Multipart,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    fs::create_dir_all(&app.cfg.upload_dir).ok();
     let mut saved = vec![];
+    let mut items = vec![];
+    let mut errors = vec![];
 
-    while let Some(field) = mp
-        .next_field()
-        .await
-        .map_err(|e| AppError::BadRequest(e.to_string()))?
-    {
-        let fname = field
-            .file_name()
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| format!("f-{}", Uuid::new_v4()));
-        let data = field
-            .bytes()
-            .await
-            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    while let Some(field) = mp.next_field().await.map_err(|e| multipart_error(&app, e))? {
+        let filename = media::display_name(field.file_name().unwrap_or_default());
+        match save_upload(&app, &user_id, &filename, field).await {
+            Ok(item) => {
+                saved.push(item["url"].clone()); // 生产环境建议交给 Nginx 静态托管
+                items.push(item);
+            }
+            Err(AppError::BadRequest(msg)) => {
+                errors.push(serde_json::json!({ "filename": filename, "error": msg }));
+            }
+            Err(e) => return Err(e),
+        }
+    }
 
-        let mut path = PathBuf::from(&app.cfg.upload_dir);
-        path.push(&fname);
-        tokio::fs::write(&path, &data)
-            .await
-            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    if items.is_empty() && !errors.is_empty() {
+        let all: Vec<String> = errors
+            .iter()
+            .map(|e| format!("{}: {}", e["filename"].as_str().unwrap_or(""), e["error"].as_str().unwrap_or("")))
+            .collect();
+        return Err(AppError::BadRequest(all.join("; ")));
+    }
+    Ok(Json(serde_json::json!({ "files": saved, "media": items, "errors": errors })))
+}
+
+/// 整个请求超过 UPLOAD_MAX_REQUEST 时给出明确提示
+fn multipart_error(app: &AppState, e: MultipartError) -> AppError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::BadRequest(format!(
+            "upload request is larger than {}",
+            media::human_size(app.cfg.upload_limits.request)
+        ))
+    } else {
+        AppError::BadRequest(e.body_text())
+    }
+}
 
-        let url = format!("/uploads/{}", fname); // 生产环境建议交给 Nginx 静态托管
-        saved.push(url);
+/// 校验并保存一个上传文件，返回媒体库记录
+async fn save_upload(
+    app: &AppState,
+    user_id: &str,
+    filename: &str,
+    mut field: Field<'_>,
+) -> Result<serde_json::Value, AppError> {
+    let ext = media::extension(filename).map_err(AppError::BadRequest)?;
+    let kind = media::Kind::of(&ext);
+    let limit = app.cfg.upload_limits.of(kind);
+    // 边读边数，超限立即放弃，不把整个大文件读进内存
+    let mut data = Vec::new();
+    while let Some(chunk) = field.chunk().await.map_err(|e| multipart_error(app, e))? {
+        if (data.len() + chunk.len()) as u64 > limit {
+            return Err(AppError::BadRequest(format!(
+                "{} files are limited to {}",
+                kind.name(),
+                media::human_size(limit)
+            )));
+        }
+        data.extend_from_slice(&chunk);
     }
+    media::check_content(&ext, &data).map_err(AppError::BadRequest)?;
+
+    // 图片按 EXIF 方向摆正、去掉 EXIF（GPS 等）；存的是处理后的内容
+    let keep_exif = app.cfg.images.keep_exif;
+    let data = tokio::task::spawn_blocking(move || {
+        images::prepare(&data, keep_exif).map(|p| p.unwrap_or(data))
+    })
+    .await
+    .map_err(anyhow::Error::from)?
+    .map_err(AppError::BadRequest)?;
 
-    Ok(Json(serde_json::json!({ "files": saved })))
+    let (hash, _) = media::store(&*app.cfg.storage, &ext, &data)
+        .await
+        .map_err(anyhow::Error::from)?;
+    let (id, size, ts) = (new_id(), data.len() as i64, now());
+    let mime = media::mime(&ext);
+    let (width, height) = media::dimensions(&data).unzip();
+    let inserted = sqlx::query!(
+        "INSERT OR IGNORE INTO media
+           (id, owner_id, hash, ext, filename, size, created_at, mime, width, height)
+         VALUES (?,?,?,?,?,?,?,?,?,?)",
+        id,
+        user_id,
+        hash,
+        ext,
+        filename,
+        size,
+        ts,
+        mime,
+        width,
+        height
+    )
+    .execute(&app.db)
+    .await?
+    .rows_affected()
+        > 0;
+    let row = sqlx::query(&format!("{} WHERE m.owner_id = ? AND m.hash = ?", MEDIA_SELECT))
+        .bind(user_id)
+        .bind(&hash)
+        .fetch_one(&app.db)
+        .await?;
+
+    // 缩小版和 WebP / AVIF 在后台生成，地址先返回
+    if width.is_some() {
+        let (settings, storage) = (app.cfg.images.clone(), app.cfg.storage.clone());
+        let key = media::rel_path(&hash, &row.get::<String, _>("ext"));
+        tokio::spawn(async move {
+            for (file, e) in images::generate(&settings, &*storage, &key, data).await {
+                tracing::warn!("image variant {}: {}", file, e);
+            }
+        });
+    }
+    let mut item = media_json(&row);
+    item["duplicate"] = serde_json::json!(!inserted);
+    item["variants"] = variants_json(app, &item);
+    Ok(item)
 }
 
-fn slugify(title: &str) -> String {
-    let s = title.trim().to_lowercase();
-    let re = Regex::new(r"[^a-z0-9]+").unwrap();
-    let s = re.replace_all(&s, "-").to_string();
-    s.trim_matches('-').to_string()
+// 媒体库的一条记录，附带引用它（同一内容哈希）的文章数
+const MEDIA_SELECT: &str = "SELECT m.id, m.owner_id, m.hash, m.ext, m.filename, m.mime, m.size,
+        m.width, m.height, m.alt, m.created_at,
+        (SELECT COUNT(*) FROM post_media pm WHERE pm.hash = m.hash) AS used_by
+   FROM media m";
+
+fn media_json(r: &SqliteRow) -> serde_json::Value {
+    let (hash, ext) = (r.get::<String, _>("hash"), r.get::<String, _>("ext"));
+    serde_json::json!({
+        "id":         r.get::<String, _>("id"),
+        "owner_id":   r.get::<String, _>("owner_id"),
+        "url":        media::url(&hash, &ext),
+        "hash":       hash,
+        "filename":   r.get::<String, _>("filename"),
+        "mime":       r.get::<String, _>("mime"),
+        "size":       r.get::<i64, _>("size"),
+        "width":      r.get::<Option<i64>, _>("width"),
+        "height":     r.get::<Option<i64>, _>("height"),
+        "alt":        r.get::<String, _>("alt"),
+        "created_at": r.get::<String, _>("created_at"),
+        "used_by":    r.get::<i64, _>("used_by"),
+    })
 }
 
-async fn ensure_tag(
-    tx: &mut Transaction<'_, sqlx::Sqlite>,
-    name: &str,
-) -> Result<String, AppError> {
-    let slug = slugify(name);
-    let row = sqlx::query!(
-        r#"SELECT id as "id!: String" FROM tags WHERE slug = ?"#,
-        slug
-    )
-    .fetch_optional(&mut **tx)
-    .await?;
+/// 图片的派生文件地址（缩小版、WebP / AVIF），可在正文里直接引用
+fn variants_json(app: &AppState, item: &serde_json::Value) -> serde_json::Value {
+    let (Some(url), Some(width), Some(mime)) =
+        (item["url"].as_str(), item["width"].as_i64(), item["mime"].as_str())
+    else {
+        return serde_json::json!([]);
+    };
+    images::planned(&app.cfg.images, url, width as u32, mime)
+        .into_iter()
+        .map(|(url, width, mime)| serde_json::json!({ "url": url, "width": width, "mime": mime }))
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct MediaParams {
+    pub q: Option<String>,    // 按文件名 / 替代文字搜索
+    pub kind: Option<String>, // image|audio|video|pdf
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
 
-    if let Some(row) = row {
-        return Ok(row.id);
+/// GET /api/media —— 自己上传的媒体（管理员为全站），新的在前
+pub async fn list_media(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Query(p): Query<MediaParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let page = p.page.unwrap_or(1).max(1);
+    let size = p.page_size.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * size;
+
+    let mut qb = QueryBuilder::<Sqlite>::new(MEDIA_SELECT);
+    qb.push(" WHERE 1 = 1");
+    if !auth::is_admin(&app, &user_id).await? {
+        qb.push(" AND m.owner_id = ").push_bind(&user_id);
     }
+    if let Some(q) = p.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let like = format!("%{}%", q);
+        qb.push(" AND (m.filename LIKE ")
+            .push_bind(like.clone())
+            .push(" OR m.alt LIKE ")
+            .push_bind(like)
+            .push(")");
+    }
+    match p.kind.as_deref() {
+        Some("image") => { qb.push(" AND m.mime LIKE 'image/%'"); }
+        Some("audio") => { qb.push(" AND m.mime LIKE 'audio/%'"); }
+        Some("video") => { qb.push(" AND m.mime LIKE 'video/%'"); }
+        Some("pdf") => { qb.push(" AND m.mime = 'application/pdf'"); }
+        _ => {} // 不传或非法值：不加过滤
+    }
+    qb.push(" ORDER BY m.created_at DESC, m.id");
+    qb.push(" LIMIT ").push_bind(size).push(" OFFSET ").push_bind(offset);
 
-    let id = Uuid::new_v4().to_string();
-    sqlx::query!(
-        "INSERT INTO tags (id, slug, name) VALUES (?,?,?)",
-        id,
-        slug,
-        name
+    let rows = qb.build().fetch_all(&app.db).await?;
+    Ok(Json(serde_json::json!({
+        "page": page,
+        "page_size": size,
+        "items": rows.iter().map(media_json).collect::<Vec<_>>(),
+    })))
+}
+
+/// 读取一条媒体记录；只有上传者本人（或管理员）可见
+async fn own_media(app: &AppState, user_id: &str, id: &str) -> Result<SqliteRow, AppError> {
+    let row = sqlx::query(&format!("{} WHERE m.id = ?", MEDIA_SELECT))
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if row.get::<String, _>("owner_id") != user_id && !auth::is_admin(app, user_id).await? {
+        return Err(AppError::Forbidden);
+    }
+    Ok(row)
+}
+
+/// 引用这个文件的文章：scope 为 Some(user_id) 时只列出这个用户自己的文章，别人的只给篇数
+/// （同一内容可能被别的作者上传引用，不能借此看到别人草稿的标题）
+async fn media_usage(
+    app: &AppState,
+    hash: &str,
+    scope: Option<&str>,
+) -> Result<(Vec<serde_json::Value>, usize), AppError> {
+    let rows = sqlx::query!(
+        r#"SELECT p.id as "id!: String", p.slug, p.title, p.author_id
+             FROM post_media pm JOIN posts p ON p.id = pm.post_id
+            WHERE pm.hash = ? ORDER BY p.created_at"#,
+        hash
     )
-    .execute(&mut **tx)
+    .fetch_all(&app.db)
     .await?;
+    let (own, others): (Vec<_>, Vec<_>) =
+        rows.into_iter().partition(|r| scope.is_none_or(|u| r.author_id == u));
+    Ok((
+        own.into_iter()
+            .map(|r| serde_json::json!({ "id": r.id, "slug": r.slug, "title": r.title }))
+            .collect(),
+        others.len(),
+    ))
+}
+
+/// 非管理员只看得到自己的文章
+async fn usage_scope<'a>(app: &AppState, user_id: &'a str) -> Result<Option<&'a str>, AppError> {
+    Ok(if auth::is_admin(app, user_id).await? { None } else { Some(user_id) })
+}
+
+/// GET /api/media/:id —— 详情，附带引用它的文章
+pub async fn get_media(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let row = own_media(&app, &user_id, &id).await?;
+    let mut item = media_json(&row);
+    item["variants"] = variants_json(&app, &item);
+    let scope = usage_scope(&app, &user_id).await?;
+    let (posts, others) = media_usage(&app, &row.get::<String, _>("hash"), scope).await?;
+    item["posts"] = serde_json::json!(posts);
+    item["other_posts"] = serde_json::json!(others);
+    Ok(Json(item))
+}
+
+#[derive(Deserialize)]
+pub struct MediaPatch {
+    pub alt: String,
+}
+
+/// PUT /api/media/:id —— 修改替代文字
+pub async fn update_media(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Path(id): Path<String>,
+    Json(patch): Json<MediaPatch>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    own_media(&app, &user_id, &id).await?;
+    let alt = patch.alt.trim();
+    sqlx::query!("UPDATE media SET alt = ? WHERE id = ?", alt, id)
+        .execute(&app.db)
+        .await?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
 
-    Ok(id)
+#[derive(Deserialize)]
+pub struct DeleteMediaParams {
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// DELETE /api/media/:id?force=true
+/// 同一内容没有别的上传记录时连文件一起删；这时仍有文章引用就拒绝（409），force=true 则照删并返回警告
+pub async fn delete_media(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Path(id): Path<String>,
+    Query(p): Query<DeleteMediaParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let row = own_media(&app, &user_id, &id).await?;
+    let (hash, ext) = (row.get::<String, _>("hash"), row.get::<String, _>("ext"));
+    let shared = sqlx::query_scalar!("SELECT COUNT(*) FROM media WHERE hash = ? AND id != ?", hash, id)
+        .fetch_one(&app.db)
+        .await?
+        > 0;
+
+    let mut warnings = vec![];
+    if !shared {
+        let scope = usage_scope(&app, &user_id).await?;
+        let (posts, others) = media_usage(&app, &hash, scope).await?;
+        let mut users: Vec<String> =
+            posts.iter().filter_map(|p| p["slug"].as_str()).map(|s| format!("post `{}`", s)).collect();
+        if others > 0 {
+            users.push(format!("{} post(s) by other authors", others));
+        }
+        if !users.is_empty() && !p.force {
+            return Err(AppError::Conflict(format!(
+                "media is used by {} post(s): {} (pass force=true to delete anyway)",
+                posts.len() + others,
+                users.join(", ")
+            )));
+        }
+        warnings.extend(users.iter().map(|s| format!("{}: still references this file", s)));
+    }
+
+    sqlx::query!("DELETE FROM media WHERE id = ?", id).execute(&app.db).await?;
+    if !shared {
+        media::remove(&*app.cfg.storage, &hash, &ext)
+            .await
+            .map_err(anyhow::Error::from)?;
+    }
+    Ok(Json(serde_json::json!({ "ok": true, "file_removed": !shared, "warnings": warnings })))
 }
 
 // GET /api/posts/:id  —— 仅作者本人可读取（用于编辑页加载原文）
@@ -296,8 +799,18 @@ pub async fn get_post(
             excerpt,
             body_md       as "body_md!: String",
             body_html     as "body_html!: String",
+            toc           as "toc!: String",
             status        as "status!: String",
             visibility    as "visibility!: String",
+            custom_fields as "custom_fields!: String",
+            password_hash IS NOT NULL as "has_password!: bool",
+            word_count      as "word_count!: i64",
+            reading_minutes as "reading_minutes!: i64",
+            auto_excerpt,
+            lang          as "lang!: String",
+            translation_group,
+            markdown_options,
+            render_options,
             published_at,
             author_id     as "author_id!: String"
         FROM posts
@@ -327,17 +840,234 @@ pub async fn get_post(
 
     let tags: Vec<String> = tag_rows.into_iter().map(|r| r.name).collect();
 
+    // 同组的其它语言版本（不限状态，编辑页用）
+    let translations = match &p.translation_group {
+        Some(group) => sqlx::query!(
+            r#"SELECT id as "id!: String", slug, title, lang, status
+                 FROM posts WHERE translation_group = ? AND id != ? ORDER BY lang"#,
+            group,
+            p.id
+        )
+        .fetch_all(&app.db)
+        .await?
+        .into_iter()
+        .map(|t| {
+            serde_json::json!({
+                "id": t.id, "slug": t.slug, "title": t.title, "lang": t.lang, "status": t.status
+            })
+        })
+        .collect(),
+        None => vec![],
+    };
+
     Ok(Json(serde_json::json!({
         "id": p.id,
         "slug": p.slug,
         "title": p.title,
         "excerpt": p.excerpt,
+        "auto_excerpt": p.auto_excerpt,
+        "word_count": p.word_count,
+        "reading_minutes": p.reading_minutes,
         "body_md": p.body_md,
         "body_html": p.body_html,
+        "toc": markdown::parse_toc(&p.toc),
         "status": p.status,
         "visibility": p.visibility,
+        "has_password": p.has_password,
+        "lang": p.lang,
+        "translation_group": p.translation_group,
+        "translations": translations,
+        "custom_fields": custom_fields::parse(&p.custom_fields),
+        // 文章自己的覆盖与上次渲染实际生效的选项
+        "markdown_options": p.markdown_options.as_deref().and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok()),
+        "render_options": p.render_options.as_deref().and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok()),
         "published_at": p.published_at,
         "author_id": p.author_id,
         "tags": tags
     })))
 }
+
+// 生成失败的原因：生成端先写这里再关闭写端，下载以错误中断，而不是拿到一个截断却看似完整的文件
+type Failure = Arc<Mutex<Option<String>>>;
+
+/// 边生成边下载：生成端往返回的 DuplexStream 里写，响应体从另一头读
+fn streaming_body() -> (DuplexStream, Failure, Body) {
+    let (writer, reader) = tokio::io::duplex(STREAM_CHUNK);
+    let failure = Failure::default();
+    let state = (reader, failure.clone(), false);
+    let stream = futures_util::stream::unfold(state, |(mut r, failure, done)| async move {
+        if done {
+            return None;
+        }
+        let mut buf = BytesMut::with_capacity(STREAM_CHUNK);
+        match r.read_buf(&mut buf).await {
+            Ok(0) => {
+                let e = failure.lock().unwrap_or_else(|e| e.into_inner()).take()?;
+                Some((Err(io::Error::other(e)), (r, failure, true)))
+            }
+            Ok(_) => Some((Ok(buf.freeze()), (r, failure, false))),
+            Err(e) => Some((Err(e), (r, failure, true))),
+        }
+    });
+    (writer, failure, Body::from_stream(stream))
+}
+
+fn fail(failure: &Failure, what: &str, e: anyhow::Error) {
+    tracing::warn!("{} failed: {:#}", what, e);
+    *failure.lock().unwrap_or_else(|e| e.into_inner()) = Some(format!("{:#}", e));
+}
+
+/// 在阻塞线程里同步写 DuplexStream（tar / gzip 只认 std::io::Write）
+struct BlockingWriter {
+    handle: Handle,
+    inner: DuplexStream,
+}
+
+impl io::Write for BlockingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.handle.block_on(self.inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.handle.block_on(self.inner.flush())
+    }
+}
+
+// GET /api/export —— 下载 Markdown 导出包（.tar.gz）；管理员导出整站，其他作者只导出自己的文章
+// 边打包边发送，上传文件逐个从存储读取
+pub async fn export_posts(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+) -> Result<(HeaderMap, Body), AppError> {
+    let scope = if auth::is_admin(&app, &user_id).await? { None } else { Some(user_id.as_str()) };
+    let export = exporter::collect(&app.db, scope).await?;
+
+    let prefix = format!("blog-export-{}", chrono::Utc::now().format("%Y%m%d"));
+    let (writer, failure, body) = streaming_body();
+    let (storage, top) = (app.cfg.storage.clone(), prefix.clone());
+    tokio::task::spawn_blocking(move || {
+        // 写端要活到记下失败原因之后
+        let mut out = BlockingWriter { handle: Handle::current(), inner: writer };
+        match exporter::write_tar_gz(&export, &*storage, &top, &mut out) {
+            Ok(missing) => {
+                for m in &missing {
+                    tracing::warn!("export: upload not found: {}", m);
+                }
+            }
+            Err(e) => fail(&failure, "export", e),
+        }
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/gzip"));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}.tar.gz\"", prefix))
+            .map_err(anyhow::Error::from)?,
+    );
+    Ok((headers, body))
+}
+
+#[derive(Deserialize)]
+pub struct BackupParams {
+    #[serde(default)]
+    pub password_hashes: bool,
+}
+
+// GET /api/backup?password_hashes=true —— 仅管理员；JSON Lines 整库备份（恢复请用 backup 命令），边导出边发送
+pub async fn backup_db(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Query(p): Query<BackupParams>,
+) -> Result<(HeaderMap, Body), AppError> {
+    if !auth::is_admin(&app, &user_id).await? {
+        return Err(AppError::Forbidden);
+    }
+    let (writer, failure, body) = streaming_body();
+    let db = app.db.clone();
+    tokio::spawn(async move {
+        let mut out = tokio::io::BufWriter::new(writer);
+        if let Err(e) = backup::dump(&db, &mut out, p.password_hashes).await {
+            fail(&failure, "backup", e);
+        }
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/x-ndjson"));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!(
+            "attachment; filename=\"blog-backup-{}.jsonl\"",
+            chrono::Utc::now().format("%Y%m%d-%H%M%S")
+        ))
+        .map_err(anyhow::Error::from)?,
+    );
+    Ok((headers, body))
+}
+
+#[derive(Deserialize)]
+pub struct RerenderParams {
+    #[serde(default)]
+    pub all: bool,
+}
+
+// GET /api/rerender —— 仅管理员；后台重新渲染任务的进度，以及当前落后的文章数
+pub async fn rerender_status(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !auth::is_admin(&app, &user_id).await? {
+        return Err(AppError::Forbidden);
+    }
+    let job = app.rerender.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    Ok(Json(serde_json::json!({
+        "renderer_version": markdown::RENDERER_VERSION,
+        "outdated": rerender::pending(&app.db, false).await?,
+        "job": job,
+    })))
+}
+
+// POST /api/rerender?all=true —— 仅管理员；在后台按批重新渲染（默认只处理版本落后的），立即返回
+pub async fn start_rerender(
+    State(app): State<AppState>,
+    AuthUser { user_id }: AuthUser,
+    Query(p): Query<RerenderParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !auth::is_admin(&app, &user_id).await? {
+        return Err(AppError::Forbidden);
+    }
+    {
+        let mut job = app.rerender.lock().unwrap_or_else(|e| e.into_inner());
+        if job.running {
+            return Err(AppError::Conflict("a re-render job is already running".into()));
+        }
+        *job = rerender::Job { running: true, all: p.all, started_at: Some(now()), ..Default::default() };
+    }
+
+    let state = app.clone();
+    let task = tokio::spawn(async move {
+        let shared = state.rerender.clone();
+        rerender::run(&state.db, &state.cfg, p.all, RERENDER_BATCH, |progress| {
+            shared.lock().unwrap_or_else(|e| e.into_inner()).progress = progress.clone();
+        })
+        .await
+    });
+    // 在另一个任务里等结果：任务 panic 时 JoinError 也会落到这里，running 不会一直卡在 true
+    let shared = app.rerender.clone();
+    tokio::spawn(async move {
+        let result = match task.await {
+            Ok(r) => r,
+            Err(e) => Err(anyhow::anyhow!("task panicked: {}", e)),
+        };
+        let mut job = shared.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = result {
+            tracing::error!("re-render job failed: {:#}", e);
+            job.progress.errors.push(format!("job aborted: {}", e));
+        }
+        job.running = false;
+        job.finished_at = Some(now());
+    });
+
+    let job = app.rerender.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    Ok(Json(serde_json::json!({ "job": job })))
+}
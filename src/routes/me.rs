@@ -5,7 +5,7 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use sqlx::{QueryBuilder, Row, Sqlite};
-use crate::{auth::AuthUser, error::AppError, state::AppState};
+use crate::{auth::AuthUser, custom_fields, error::AppError, state::AppState};
 
 #[derive(Serialize)]
 pub struct MeResp {
@@ -85,7 +85,7 @@ pub async fn update_me(
 #[derive(Deserialize, Debug)]
 pub struct MyPostsParams {
     pub status: Option<String>,        // all|published|draft
-    pub visibility: Option<String>,    // all|public|private
+    pub visibility: Option<String>,    // all|public|private|protected|unlisted
     pub page: Option<i64>,
     pub page_size: Option<i64>,
 }
@@ -102,7 +102,8 @@ pub async fn list_my_posts(
     let offset = (page - 1) * size;
 
     let mut qb = QueryBuilder::<Sqlite>::new(
-        "SELECT id, slug, title, excerpt, status, visibility, published_at
+        "SELECT id, slug, title, COALESCE(NULLIF(excerpt, ''), auto_excerpt) AS excerpt,
+                status, visibility, custom_fields, word_count, reading_minutes, lang, published_at
            FROM posts
           WHERE author_id = ",
     );
@@ -122,6 +123,8 @@ pub async fn list_my_posts(
         match v {
             "public"  => { qb.push(" AND visibility = 'public'"); }
             "private" => { qb.push(" AND visibility = 'private'"); }
+            "protected" => { qb.push(" AND visibility = 'protected'"); }
+            "unlisted" => { qb.push(" AND visibility = 'unlisted'"); }
             _ => {} // all 或非法值：不加过滤
         }
     }
@@ -141,6 +144,10 @@ pub async fn list_my_posts(
                 "excerpt":      r.try_get::<String,_>("excerpt").ok(),
                 "status":       r.get::<String,_>("status"),
                 "visibility":   r.get::<String,_>("visibility"),
+                "custom_fields": custom_fields::parse(&r.get::<String,_>("custom_fields")),
+                "word_count":   r.get::<i64,_>("word_count"),
+                "reading_minutes": r.get::<i64,_>("reading_minutes"),
+                "lang":         r.get::<String,_>("lang"),
                 "published_at": r.try_get::<String,_>("published_at").ok(),
             })
         })
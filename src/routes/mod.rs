@@ -1,9 +1,9 @@
 use axum::{
+    extract::DefaultBodyLimit,
     Router,
-    routing::{get, post},
-    // routing::{put, delete},
+    routing::{delete, get, post},
+    // routing::put,
 };
-use tower_http::services::ServeDir;
 use crate::state::AppState;
 
 pub mod public;
@@ -11,34 +11,57 @@ pub mod admin;
 pub mod me;
 
 pub fn app_router(state: AppState) -> Router {
-    let upload_dir = state.cfg.upload_dir.clone();
+    let upload_limit = state.cfg.upload_limits.request as usize;
 
     Router::new()
         // ===== public =====
         .route("/health", get(public::health))
         .route("/api/posts", get(public::list_posts))
         .route("/api/posts/slug/:slug", get(public::get_post)) // 公开详情（仅已发布+public；作者登录可看自己的 private，逻辑在 public::get_post 内实现）
+        .route("/api/posts/slug/:slug/unlock", post(public::unlock_post)) // protected 文章：密码换文章令牌
         .route("/api/tags", get(public::list_tags))
         .route("/rss.xml", get(public::rss))
         .route("/sitemap.xml", get(public::sitemap))
+        .route("/highlight.css", get(public::highlight_css)) // 代码高亮主题
 
         // ===== admin（需登录）=====
         .route("/api/auth/login", post(admin::login))
         .route("/api/posts", post(admin::create_post))
+        .route("/api/preview", post(admin::render_preview)) // 编辑器实时预览（不保存）
         // ✅ 新增 GET：作者本人按 id 读取（编辑页用）；保留 PUT/DELETE
         .route(
             "/api/posts/:id",
             get(admin::get_post).put(admin::update_post).delete(admin::delete_post)
         )
         .route("/api/posts/:id/publish", post(admin::publish_post))
-        .route("/api/media", post(admin::upload_media))
+        .route(
+            "/api/posts/:id/previews",
+            get(admin::list_previews).post(admin::create_preview)
+        )
+        .route("/api/posts/:id/previews/:preview_id", delete(admin::revoke_preview))
+        .route(
+            "/api/media",
+            get(admin::list_media)
+                .post(admin::upload_media)
+                .layer(DefaultBodyLimit::max(upload_limit)) // 单个文件另按类型限制
+        )
+        .route(
+            "/api/media/:id",
+            get(admin::get_media).put(admin::update_media).delete(admin::delete_media)
+        ) // 媒体库：详情（含引用文章）、替代文字、删除
+        .route("/api/export", get(admin::export_posts)) // Markdown 导出包（管理员为整站）
+        .route("/api/backup", get(admin::backup_db)) // JSON Lines 整库备份（仅管理员）
+        .route(
+            "/api/rerender",
+            get(admin::rerender_status).post(admin::start_rerender)
+        ) // 渲染管线升级后重新渲染旧文章（仅管理员）
 
         // me（作者自服务） 👇
         .route("/api/me", get(me::get_me).put(me::update_me))
         .route("/api/me/posts", get(me::list_my_posts))
 
-        // 静态文件（开发期本地看上传）
-        .nest_service("/uploads", ServeDir::new(upload_dir))
+        // 上传文件（开发期本地看上传；按 Accept 返回 AVIF / WebP）
+        .route("/uploads/*path", get(public::upload_file))
 
         // 共享全局状态（一次）
         .with_state(state)
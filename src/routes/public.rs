@@ -1,18 +1,44 @@
 use axum::{
-    extract::{FromRequestParts, Path, Query, State},
-    http::request::Parts,
+    extract::{ConnectInfo, FromRequestParts, Path, Query, State},
+    http::{request::Parts, HeaderMap},
+    response::{IntoResponse, Redirect},
     Json,
 };
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::Deserialize;
 use sqlx::{self, FromRow, QueryBuilder, Row, Sqlite};
-use crate::{auth::AuthUser, error::AppError, rss as rss_mod, state::AppState};
+use std::net::SocketAddr;
+use tower_http::services::ServeFile;
+use crate::{
+    auth::{self, AuthUser},
+    custom_fields,
+    db::Db,
+    error::AppError,
+    highlight,
+    images,
+    markdown,
+    media,
+    models::now,
+    rss as rss_mod,
+    state::AppState,
+    storage,
+};
 
-#[derive(Deserialize)]
+// 解锁 protected 文章后签发的文章级令牌有效期（分钟）
+const POST_TOKEN_TTL_MINUTES: i64 = 60;
+const POST_TOKEN_SCOPE: &str = "post";
+
+#[derive(Deserialize, Default)]
 pub struct ListParams {
     pub page: Option<i64>,
     pub page_size: Option<i64>,
     pub tag: Option<String>,
     pub q: Option<String>,
+    // 自定义字段过滤：?field=difficulty&value=hard（数组字段按“包含”匹配）
+    pub field: Option<String>,
+    pub value: Option<String>,
+    pub lang: Option<String>,
 }
 
 pub async fn health() -> &'static str {
@@ -38,19 +64,39 @@ where
     }
 }
 
+impl ListParams {
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn size(&self) -> i64 {
+        self.page_size.unwrap_or(10).clamp(1, 50)
+    }
+}
+
 /// GET /api/posts
-/// 仅返回「已发布 + public」的文章。支持分页、tag 与关键字搜索。
+/// 仅返回「已发布 + public」的文章。支持分页、tag、关键字搜索与自定义字段过滤。
 pub async fn list_posts(
     State(app): State<AppState>,
     Query(p): Query<ListParams>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let page = p.page.unwrap_or(1).max(1);
-    let size = p.page_size.unwrap_or(10).clamp(1, 50);
-    let offset = (page - 1) * size;
+    let items = list_items(&app.db, &p).await?;
+    Ok(Json(serde_json::json!({
+        "page": p.page(),
+        "page_size": p.size(),
+        "items": items
+    })))
+}
+
+/// 列表查询本体（API 与静态站生成共用）
+pub async fn list_items(db: &Db, p: &ListParams) -> Result<Vec<serde_json::Value>, AppError> {
+    let size = p.size();
+    let offset = (p.page() - 1) * size;
 
     // 构造一条查询
     let mut qb = QueryBuilder::<Sqlite>::new(
-        "SELECT id, slug, title, excerpt, published_at \
+        "SELECT id, slug, title, COALESCE(NULLIF(excerpt, ''), auto_excerpt) AS excerpt, \
+                custom_fields, word_count, reading_minutes, lang, published_at \
          FROM posts WHERE status='published' AND visibility='public'",
     );
 
@@ -76,14 +122,30 @@ pub async fn list_posts(
             .push(")");
     }
 
+    if let Some(ref lang) = p.lang {
+        qb.push(" AND lang = ").push_bind(lang);
+    }
+
+    if let (Some(field), Some(value)) = (p.field.as_deref(), p.value.as_deref()) {
+        if !custom_fields::valid_key(field) {
+            return Err(AppError::BadRequest(format!("invalid custom field name: {}", field)));
+        }
+        // json_each 对标量路径返回单行、对数组返回每个元素，二者统一处理
+        qb.push(" AND EXISTS (SELECT 1 FROM json_each(custom_fields, ")
+            .push_bind(custom_fields::json_path(field))
+            .push(") WHERE value = json_extract(")
+            .push_bind(custom_fields::filter_literal(value))
+            .push(", '$'))");
+    }
+
     qb.push(" ORDER BY published_at DESC LIMIT ")
         .push_bind(size)
         .push(" OFFSET ")
         .push_bind(offset);
 
-    let rows = qb.build().fetch_all(&app.db).await?;
+    let rows = qb.build().fetch_all(db).await?;
 
-    let items: Vec<serde_json::Value> = rows
+    Ok(rows
         .into_iter()
         .map(|r| {
             serde_json::json!({
@@ -91,104 +153,301 @@ pub async fn list_posts(
                 "slug": r.get::<String,_>("slug"),
                 "title": r.get::<String,_>("title"),
                 "excerpt": r.try_get::<String,_>("excerpt").ok(),
+                "custom_fields": custom_fields::parse(&r.get::<String,_>("custom_fields")),
+                "word_count": r.get::<i64,_>("word_count"),
+                "reading_minutes": r.get::<i64,_>("reading_minutes"),
+                "lang": r.get::<String,_>("lang"),
                 "published_at": r.try_get::<String,_>("published_at").ok(),
             })
         })
-        .collect();
-
-    Ok(Json(serde_json::json!({
-        "page": page,
-        "page_size": size,
-        "items": items
-    })))
+        .collect())
 }
 
 #[derive(FromRow)]
-struct PostRow {
-    id: String,
+pub(crate) struct PostRow {
+    pub(crate) id: String,
     slug: String,
     title: String,
     excerpt: Option<String>,
     body_html: String,
+    toc: String,
     published_at: Option<String>,
     author_id: String,
     visibility: String,
     status: String,
+    custom_fields: String,
+    word_count: i64,
+    reading_minutes: i64,
+    lang: String,
+    translation_group: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PostAccess {
+    // 草稿预览链接里的秘密 token
+    pub preview: Option<String>,
 }
 
 /// GET /api/posts/slug/:slug
-/// 匿名/非作者：只看 public；作者登录后：可看自己 private。
+/// 匿名/非作者：只看 public / protected / unlisted；作者登录后：可看自己 private。
+/// protected 文章在未解锁（无有效文章令牌）时只返回标题等元信息。
+/// 带有效 ?preview= 时不限状态与可见性（用于把草稿分享给审稿人）。
 pub async fn get_post(
     State(app): State<AppState>,
     MaybeUser(mu): MaybeUser,
     Path(slug): Path<String>,
+    Query(access): Query<PostAccess>,
+    headers: HeaderMap,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    let viewer = mu.map(|u| u.user_id);
+
+    let previewing = match access.preview.as_deref() {
+        Some(token) => {
+            let (ts, token_hash) = (now(), auth::token_hash(token));
+            sqlx::query_scalar!(
+                r#"SELECT COUNT(*) as "n!: i64"
+                     FROM preview_tokens pt JOIN posts p ON p.id = pt.post_id
+                    WHERE p.slug = ? AND pt.token_hash = ? AND pt.revoked_at IS NULL AND pt.expires_at > ?"#,
+                slug,
+                token_hash,
+                ts
+            )
+            .fetch_one(&app.db)
+            .await?
+                > 0
+        }
+        None => false,
+    };
+
+    let p = load_post(&app.db, &slug, viewer.as_deref(), previewing)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let translations = translations(&app.db, &p).await?;
+
+    // protected 文章的访问令牌只认 X-Post-Token 头，不放进查询串（会进访问日志、代理和 Referer）
+    let token = headers.get("x-post-token").and_then(|v| v.to_str().ok());
+    let locked = p.visibility == "protected"
+        && !previewing
+        && viewer.as_deref() != Some(p.author_id.as_str())
+        && !token.is_some_and(|t| auth::verify_scoped(t, POST_TOKEN_SCOPE, &p.id, &app.jwt_secret));
+
+    if locked {
+        return Ok(Json(serde_json::json!({
+            "id": p.id,
+            "slug": p.slug,
+            "title": p.title,
+            "published_at": p.published_at,
+            "author_id": p.author_id,
+            "visibility": p.visibility,
+            "status": p.status,
+            "word_count": p.word_count,
+            "reading_minutes": p.reading_minutes,
+            "lang": p.lang,
+            "translations": translations,
+            "locked": true
+        })));
+    }
+
+    let tags = post_tags(&app.db, &p.id).await?;
+    let backlinks = backlinks(&app.db, &p).await?;
+    Ok(Json(post_json(p, translations, tags, backlinks, previewing)))
+}
+
+/// 按 slug 取文章；previewing 时不限状态与可见性，否则只看已发布的 public / protected / unlisted（作者还能看自己的 private）
+pub(crate) async fn load_post(
+    db: &Db,
+    slug: &str,
+    viewer: Option<&str>,
+    previewing: bool,
+) -> Result<Option<PostRow>, AppError> {
     let mut qb = QueryBuilder::<Sqlite>::new(
-        "SELECT id, slug, title, excerpt, body_html, published_at, author_id, visibility, status \
+        "SELECT id, slug, title, COALESCE(NULLIF(excerpt, ''), auto_excerpt) AS excerpt, body_html, toc, \
+                published_at, author_id, visibility, status, custom_fields, word_count, reading_minutes, \
+                lang, translation_group \
          FROM posts WHERE slug = ",
     );
-    qb.push_bind(&slug);
-    qb.push(" AND status = 'published'");
+    qb.push_bind(slug);
 
-    if let Some(AuthUser { user_id }) = mu {
-        qb.push(" AND (visibility='public' OR author_id = ")
-            .push_bind(user_id)
-            .push(")");
-    } else {
-        qb.push(" AND visibility='public'");
+    if !previewing {
+        qb.push(" AND status = 'published'");
+        if let Some(user_id) = viewer {
+            qb.push(" AND (visibility IN ('public','protected','unlisted') OR author_id = ")
+                .push_bind(user_id)
+                .push(")");
+        } else {
+            qb.push(" AND visibility IN ('public','protected','unlisted')");
+        }
     }
 
-    let rec: Option<PostRow> = qb
-        .build_query_as::<PostRow>()
-        .fetch_optional(&app.db)
-        .await?;
+    Ok(qb.build_query_as::<PostRow>().fetch_optional(db).await?)
+}
 
-    let p = rec.ok_or(AppError::NotFound)?;
-    Ok(Json(serde_json::json!({
+/// 其它语言版本：只列出读者能看到的（已发布且非 private）
+pub(crate) async fn translations(db: &Db, p: &PostRow) -> Result<Vec<serde_json::Value>, AppError> {
+    let Some(group) = &p.translation_group else { return Ok(vec![]) };
+    Ok(sqlx::query!(
+        r#"SELECT slug, title, lang FROM posts
+            WHERE translation_group = ? AND id != ?
+              AND status = 'published' AND visibility != 'private'
+            ORDER BY lang"#,
+        group,
+        p.id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|t| serde_json::json!({ "lang": t.lang, "slug": t.slug, "title": t.title }))
+    .collect())
+}
+
+pub(crate) async fn post_tags(db: &Db, post_id: &str) -> Result<Vec<serde_json::Value>, AppError> {
+    Ok(sqlx::query!(
+        "SELECT t.id, t.slug, t.name FROM tags t JOIN post_tags pt ON pt.tag_id = t.id WHERE pt.post_id = ? ORDER BY t.name",
+        post_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|t| serde_json::json!({ "id": t.id, "slug": t.slug, "name": t.name }))
+    .collect())
+}
+
+/// 链接到这篇文章的其它文章（只列已发布的 public；保存后才改了 slug 的也算）
+pub(crate) async fn backlinks(db: &Db, p: &PostRow) -> Result<Vec<serde_json::Value>, AppError> {
+    Ok(sqlx::query!(
+        r#"SELECT DISTINCT s.slug, s.title, s.published_at
+             FROM post_links l JOIN posts s ON s.id = l.source_id
+            WHERE (l.target_id = ? OR (l.target_id IS NULL AND l.target_slug = ?))
+              AND s.id != ? AND s.status = 'published' AND s.visibility = 'public'
+            ORDER BY s.published_at DESC"#,
+        p.id,
+        p.slug,
+        p.id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|b| serde_json::json!({ "slug": b.slug, "title": b.title, "published_at": b.published_at }))
+    .collect())
+}
+
+/// 已解锁文章的完整 JSON
+pub(crate) fn post_json(
+    p: PostRow,
+    translations: Vec<serde_json::Value>,
+    tags: Vec<serde_json::Value>,
+    backlinks: Vec<serde_json::Value>,
+    previewing: bool,
+) -> serde_json::Value {
+    serde_json::json!({
         "id": p.id,
         "slug": p.slug,
         "title": p.title,
         "excerpt": p.excerpt,
         "body_html": p.body_html,
+        "toc": markdown::parse_toc(&p.toc),
         "published_at": p.published_at,
         "author_id": p.author_id,
         "visibility": p.visibility,
-        "status": p.status
+        "status": p.status,
+        "custom_fields": custom_fields::parse(&p.custom_fields),
+        "word_count": p.word_count,
+        "reading_minutes": p.reading_minutes,
+        "lang": p.lang,
+        "tags": tags,
+        "translations": translations,
+        "backlinks": backlinks,
+        "locked": false,
+        "preview": previewing
+    })
+}
+
+#[derive(Deserialize)]
+pub struct UnlockInput {
+    pub password: String,
+}
+
+/// POST /api/posts/slug/:slug/unlock
+/// 校验 protected 文章密码，返回短期文章令牌（请求详情时放在 X-Post-Token 头里）
+/// 同一 IP 对同一篇文章连续猜错后按指数退避锁定（429 + Retry-After）
+pub async fn unlock_post(
+    State(app): State<AppState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    Path(slug): Path<String>,
+    Json(inp): Json<UnlockInput>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let row = sqlx::query!(
+        r#"SELECT id as "id!: String", password_hash
+             FROM posts
+            WHERE slug = ? AND status = 'published' AND visibility = 'protected'"#,
+        slug
+    )
+    .fetch_optional(&app.db)
+    .await?;
+
+    let r = row.ok_or(AppError::NotFound)?;
+    let key = format!("{}|{}", r.id, peer.map_or("-".into(), |ConnectInfo(addr)| addr.ip().to_string()));
+    if let Err(wait) = app.unlock_throttle.check(&key) {
+        return Err(AppError::TooManyRequests(wait.as_secs() + 1));
+    }
+    let hash = r.password_hash.ok_or(AppError::Unauthorized)?;
+    if !auth::verify_password(&inp.password, &hash) {
+        app.unlock_throttle.failed(&key);
+        return Err(AppError::Unauthorized);
+    }
+    app.unlock_throttle.succeeded(&key);
+
+    let token = auth::sign_scoped(&r.id, POST_TOKEN_SCOPE, &app.jwt_secret, POST_TOKEN_TTL_MINUTES)?;
+    Ok(Json(serde_json::json!({
+        "access_token": token,
+        "expires_in": POST_TOKEN_TTL_MINUTES * 60
     })))
 }
 
 /// GET /api/tags
+/// 计数只统计「已发布 + public」文章（unlisted / private 等不计入）
 pub async fn list_tags(State(app): State<AppState>) -> Result<Json<Vec<serde_json::Value>>, AppError> {
+    Ok(Json(tag_counts(&app.db).await?))
+}
+
+pub async fn tag_counts(db: &Db) -> Result<Vec<serde_json::Value>, AppError> {
     let rows = sqlx::query!(
         r#"
-        SELECT t.id, t.slug, t.name, COUNT(pt.post_id) as cnt
-          FROM tags t LEFT JOIN post_tags pt ON t.id = pt.tag_id
+        SELECT t.id, t.slug, t.name, COUNT(p.id) as cnt
+          FROM tags t
+          LEFT JOIN post_tags pt ON t.id = pt.tag_id
+          LEFT JOIN posts p ON p.id = pt.post_id AND p.status = 'published' AND p.visibility = 'public'
          GROUP BY t.id
          ORDER BY cnt DESC
         "#
     )
-    .fetch_all(&app.db)
+    .fetch_all(db)
     .await?;
 
-    Ok(Json(
-        rows.into_iter()
-            .map(|r| {
-                serde_json::json!({
-                    "id": r.id,
-                    "slug": r.slug,
-                    "name": r.name,
-                    "count": r.cnt
-                })
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            serde_json::json!({
+                "id": r.id,
+                "slug": r.slug,
+                "name": r.name,
+                "count": r.cnt
             })
-            .collect(),
-    ))
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+pub struct FeedParams {
+    pub lang: Option<String>,
 }
 
-/// GET /rss.xml
+/// GET /rss.xml（?lang=en 输出单一语言的订阅）
 pub async fn rss(
     State(app): State<AppState>,
+    Query(f): Query<FeedParams>,
 ) -> Result<(axum::http::HeaderMap, String), AppError> {
-    let xml = rss_mod::build_rss(&app.db, &app.cfg).await?;
+    let xml = rss_mod::build_rss(&app.db, &app.cfg, f.lang.as_deref()).await?;
     let mut headers = axum::http::HeaderMap::new();
     headers.insert(
         axum::http::header::CONTENT_TYPE,
@@ -209,3 +468,302 @@ pub async fn sitemap(
     );
     Ok((headers, xml))
 }
+
+/// GET /highlight.css（HIGHLIGHT_THEME 对应的代码高亮样式）
+pub async fn highlight_css(State(app): State<AppState>) -> (axum::http::HeaderMap, String) {
+    let css = highlight::css(&app.cfg.highlight_theme).unwrap_or_default();
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        "text/css; charset=utf-8".parse().unwrap(),
+    );
+    headers.insert(
+        axum::http::header::CACHE_CONTROL,
+        "public, max-age=3600".parse().unwrap(),
+    );
+    (headers, css)
+}
+
+/// GET /uploads/*path —— 上传文件（开发期本地看上传；生产环境建议交给 Nginx）
+/// JPEG / PNG 在浏览器接受 AVIF / WebP 且已生成时换成对应格式（Vary: Accept）；
+/// 文件名带内容哈希，内容不会变，可以长期缓存。
+/// 本地存储直接发文件；对象存储按 S3_DOWNLOADS 跳转到预签名地址，或读出来转发
+pub async fn upload_file(
+    State(app): State<AppState>,
+    Path(path): Path<String>,
+    req: axum::extract::Request,
+) -> Result<axum::response::Response, AppError> {
+    let storage = &*app.cfg.storage;
+    let mut key = storage::key(&path).ok_or(AppError::NotFound)?.to_string();
+    let accept = req
+        .headers()
+        .get(axum::http::header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let negotiable = matches!(
+        path.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).as_deref(),
+        Some("jpg" | "png" | "webp")
+    );
+    if let Some(alt) = images::negotiate(&app.cfg.images, storage, &key, accept).await {
+        key = alt;
+    }
+
+    let mut res = if let Some(file) = storage.local_path(&key) {
+        let res = ServeFile::new(&file)
+            .try_call(req)
+            .await
+            .map_err(anyhow::Error::from)?;
+        res.map(axum::body::Body::new)
+    } else if let Some(url) = storage.presigned_url(&key) {
+        // 跳转目标随 Accept 变化，只许浏览器自己短暂缓存
+        let mut res = Redirect::temporary(&url).into_response();
+        res.headers_mut().insert(
+            axum::http::header::CACHE_CONTROL,
+            "private, max-age=60".parse().unwrap(),
+        );
+        if negotiable {
+            res.headers_mut().insert(axum::http::header::VARY, "Accept".parse().unwrap());
+        }
+        return Ok(res);
+    } else {
+        // 边读边转发；Range / If-None-Match 交给存储处理，状态码和 ETag、Content-Range 等原样带回
+        let forward: Vec<(&str, &str)> = storage::FORWARD_REQUEST
+            .iter()
+            .filter_map(|name| Some((*name, req.headers().get(*name)?.to_str().ok()?)))
+            .collect();
+        let download = storage
+            .get_stream(&key, &forward)
+            .await
+            .map_err(anyhow::Error::from)?
+            .ok_or(AppError::NotFound)?;
+        let mut res = axum::body::Body::from_stream(download.body).into_response();
+        *res.status_mut() = axum::http::StatusCode::from_u16(download.status).map_err(anyhow::Error::from)?;
+        let headers = res.headers_mut();
+        for (name, value) in download.headers {
+            if let Ok(value) = value.parse() {
+                headers.insert(name, value);
+            }
+        }
+        let ext = key.rsplit_once('.').map_or(String::new(), |(_, e)| e.to_ascii_lowercase());
+        headers.insert(axum::http::header::CONTENT_TYPE, media::mime(&ext).parse().unwrap());
+        res
+    };
+    let headers = res.headers_mut();
+    if negotiable {
+        headers.insert(axum::http::header::VARY, "Accept".parse().unwrap());
+    }
+    if RE_HASHED.is_match(&path) {
+        headers.insert(
+            axum::http::header::CACHE_CONTROL,
+            "public, max-age=31536000, immutable".parse().unwrap(),
+        );
+    }
+    // 上传文件和站点同源：不许浏览器猜类型；SVG 即便漏过了上传检查，也不能执行脚本、加载外部资源
+    headers.insert(axum::http::header::X_CONTENT_TYPE_OPTIONS, "nosniff".parse().unwrap());
+    if key.to_ascii_lowercase().ends_with(".svg") {
+        headers.insert(
+            axum::http::header::CONTENT_SECURITY_POLICY,
+            "default-src 'none'; style-src 'unsafe-inline'; sandbox".parse().unwrap(),
+        );
+    }
+    Ok(res)
+}
+
+// 按内容哈希存放的文件（见 media.rs），含派生的缩小版
+static RE_HASHED: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[0-9a-f]{2}/[0-9a-f]{2}/[0-9a-f]{64}\.").unwrap());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    async fn fetch(
+        app: &AppState,
+        slug: &str,
+        viewer: Option<&str>,
+        preview: Option<&str>,
+        post_token: Option<&str>,
+    ) -> Result<serde_json::Value, AppError> {
+        let mut headers = HeaderMap::new();
+        if let Some(t) = post_token {
+            headers.insert("x-post-token", t.parse().unwrap());
+        }
+        let Json(v) = get_post(
+            State(app.clone()),
+            MaybeUser(viewer.map(|id| AuthUser { user_id: id.into() })),
+            Path(slug.into()),
+            Query(PostAccess { preview: preview.map(Into::into) }),
+            headers,
+        )
+        .await?;
+        Ok(v)
+    }
+
+    async fn unlock(app: &AppState, slug: &str, password: &str) -> Result<String, AppError> {
+        let Json(v) = unlock_post(
+            State(app.clone()),
+            None,
+            Path(slug.into()),
+            Json(UnlockInput { password: password.into() }),
+        )
+        .await?;
+        Ok(v["access_token"].as_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn protected_needs_unlock() {
+        let db = testing::db().await;
+        let app = testing::state(&db);
+        let alice = testing::user(&db, "alice").await;
+        let body = serde_json::json!({
+            "title": "Secret", "slug": "secret", "body_md": "hidden text",
+            "visibility": "protected", "password": "pw"
+        });
+        testing::post(&db, &app.cfg, &alice, body).await.unwrap();
+
+        let v = fetch(&app, "secret", None, None, None).await.unwrap();
+        assert_eq!(v["locked"], true);
+        assert_eq!(v["title"], "Secret");
+        assert!(v.get("body_html").is_none());
+
+        assert!(matches!(unlock(&app, "secret", "nope").await, Err(AppError::Unauthorized)));
+        let token = unlock(&app, "secret", "pw").await.unwrap();
+        let v = fetch(&app, "secret", None, None, Some(&token)).await.unwrap();
+        assert_eq!(v["locked"], false);
+        assert!(v["body_html"].as_str().unwrap().contains("hidden text"));
+
+        // 作者自己不用解锁
+        let v = fetch(&app, "secret", Some(&alice), None, None).await.unwrap();
+        assert_eq!(v["locked"], false);
+    }
+
+    #[tokio::test]
+    async fn post_token_scope_and_subject() {
+        let db = testing::db().await;
+        let app = testing::state(&db);
+        let alice = testing::user(&db, "alice").await;
+        let bob = testing::user(&db, "bob").await;
+        let mut ids = vec![];
+        for slug in ["one", "two"] {
+            let body = serde_json::json!({
+                "title": slug, "slug": slug, "body_md": "x", "visibility": "protected", "password": "pw"
+            });
+            ids.push(testing::post(&db, &app.cfg, &alice, body).await.unwrap());
+        }
+        let token = unlock(&app, "one", "pw").await.unwrap();
+        assert_eq!(fetch(&app, "one", None, None, Some(&token)).await.unwrap()["locked"], false);
+        // 别的文章的令牌、登录令牌、别的密钥签的令牌都不能当解锁令牌用
+        assert_eq!(fetch(&app, "two", None, None, Some(&token)).await.unwrap()["locked"], true);
+        let login = auth::sign(&bob, &app.jwt_secret, 60).unwrap();
+        assert_eq!(fetch(&app, "one", Some(&bob), None, Some(&login)).await.unwrap()["locked"], true);
+        let forged = auth::sign_scoped(&ids[0], POST_TOKEN_SCOPE, "other-secret", 60).unwrap();
+        assert_eq!(fetch(&app, "one", None, None, Some(&forged)).await.unwrap()["locked"], true);
+    }
+
+    #[tokio::test]
+    async fn unlisted_and_private() {
+        let db = testing::db().await;
+        let app = testing::state(&db);
+        let alice = testing::user(&db, "alice").await;
+        let bob = testing::user(&db, "bob").await;
+        for (slug, visibility) in [("open", "public"), ("hidden", "unlisted"), ("mine", "private")] {
+            let body = serde_json::json!({
+                "title": slug, "slug": slug, "body_md": "x", "visibility": visibility, "tags": ["t"]
+            });
+            testing::post(&db, &app.cfg, &alice, body).await.unwrap();
+        }
+
+        let items = list_items(&db, &ListParams::default()).await.unwrap();
+        let slugs: Vec<_> = items.iter().map(|i| i["slug"].as_str().unwrap()).collect();
+        assert_eq!(slugs, ["open"]);
+        assert_eq!(tag_counts(&db).await.unwrap()[0]["count"], 1);
+
+        // unlisted 知道地址就能看
+        assert_eq!(fetch(&app, "hidden", None, None, None).await.unwrap()["locked"], false);
+        // private 只有作者能看
+        assert!(matches!(fetch(&app, "mine", None, None, None).await, Err(AppError::NotFound)));
+        assert!(matches!(fetch(&app, "mine", Some(&bob), None, None).await, Err(AppError::NotFound)));
+        assert_eq!(fetch(&app, "mine", Some(&alice), None, None).await.unwrap()["slug"], "mine");
+    }
+
+    #[tokio::test]
+    async fn custom_field_filter() {
+        let db = testing::db().await;
+        let app = testing::state(&db);
+        let alice = testing::user(&db, "alice").await;
+        for (slug, fields) in [
+            ("a", serde_json::json!({ "difficulty": "hard", "topics": ["rust", "sql"], "rating": 5 })),
+            ("b", serde_json::json!({ "difficulty": "easy", "topics": ["rust"], "rating": "5" })),
+            ("c", serde_json::json!({ "featured": true, "topics": "rust" })),
+        ] {
+            let body = serde_json::json!({ "title": slug, "slug": slug, "body_md": "x", "custom_fields": fields });
+            testing::post(&db, &app.cfg, &alice, body).await.unwrap();
+        }
+        let filter = |field: &str, value: &str| {
+            let p = ListParams { field: Some(field.into()), value: Some(value.into()), ..Default::default() };
+            let db = db.clone();
+            async move {
+                let items = list_items(&db, &p).await?;
+                let mut slugs: Vec<String> = items.iter().map(|i| i["slug"].as_str().unwrap().into()).collect();
+                slugs.sort();
+                Ok::<_, AppError>(slugs)
+            }
+        };
+        assert_eq!(filter("difficulty", "hard").await.unwrap(), ["a"]);
+        // 数组按“包含”匹配，标量也照样比较
+        assert_eq!(filter("topics", "rust").await.unwrap(), ["a", "b", "c"]);
+        assert_eq!(filter("topics", "sql").await.unwrap(), ["a"]);
+        // 数字、布尔按类型比较，不和字符串 "5" 混淆
+        assert_eq!(filter("rating", "5").await.unwrap(), ["a"]);
+        assert_eq!(filter("featured", "true").await.unwrap(), ["c"]);
+        assert!(filter("missing", "x").await.unwrap().is_empty());
+        assert!(matches!(filter("a.b", "x").await, Err(AppError::BadRequest(_))));
+        assert!(matches!(filter("x\"')", "x").await, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn preview_tokens() {
+        let db = testing::db().await;
+        let app = testing::state(&db);
+        let alice = testing::user(&db, "alice").await;
+        let mut ids = vec![];
+        for slug in ["draft", "other"] {
+            let body = serde_json::json!({ "title": slug, "slug": slug, "body_md": "x", "status": "draft" });
+            ids.push(testing::post(&db, &app.cfg, &alice, body).await.unwrap());
+        }
+        let future = (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+        let past = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+        for (token, post_id, expires_at, revoked_at) in [
+            ("good", &ids[0], &future, None),
+            ("expired", &ids[0], &past, None),
+            ("revoked", &ids[0], &future, Some(now())),
+            ("elsewhere", &ids[1], &future, None),
+        ] {
+            sqlx::query(
+                "INSERT INTO preview_tokens (id, token_hash, post_id, created_by, created_at, expires_at, revoked_at) \
+                 VALUES (?,?,?,?,?,?,?)",
+            )
+            .bind(crate::models::new_id())
+            .bind(auth::token_hash(token))
+            .bind(post_id)
+            .bind(&alice)
+            .bind(now())
+            .bind(expires_at)
+            .bind(revoked_at)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        let v = fetch(&app, "draft", None, Some("good"), None).await.unwrap();
+        assert_eq!(v["preview"], true);
+        assert_eq!(v["status"], "draft");
+        for token in ["expired", "revoked", "elsewhere", "nope"] {
+            let r = fetch(&app, "draft", None, Some(token), None).await;
+            assert!(matches!(r, Err(AppError::NotFound)), "{}", token);
+        }
+        // 草稿不带预览 token 时谁都看不到（作者走后台接口）
+        assert!(matches!(fetch(&app, "draft", None, None, None).await, Err(AppError::NotFound)));
+    }
+}
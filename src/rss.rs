@@ -1,7 +1,12 @@
+use std::collections::HashMap;
+
 use crate::{db::Db, config::Config};
-pub async fn build_rss(db: &Db, cfg: &Config) -> anyhow::Result<String> {
+
+/// lang 为 Some 时只输出该语言的文章
+pub async fn build_rss(db: &Db, cfg: &Config, lang: Option<&str>) -> anyhow::Result<String> {
     let rows = sqlx::query!(
-      "SELECT slug,title,excerpt,published_at FROM posts WHERE status='published' ORDER BY published_at DESC LIMIT 50"
+      "SELECT slug,title,COALESCE(NULLIF(excerpt,''),auto_excerpt) AS \"excerpt: String\",published_at FROM posts WHERE status='published' AND visibility='public' AND (? IS NULL OR lang = ?) ORDER BY published_at DESC LIMIT 50",
+      lang, lang
     ).fetch_all(db).await?;
     let mut items = String::new();
     for r in rows {
@@ -12,22 +17,50 @@ pub async fn build_rss(db: &Db, cfg: &Config) -> anyhow::Result<String> {
           t=r.title, l=link, p=pubdate, d=r.excerpt.unwrap_or_default()
         ));
     }
+    let language = lang.map(|l| format!("<language>{}</language>", xml_escape(l))).unwrap_or_default();
     Ok(format!(r#"<?xml version="1.0" encoding="utf-8"?>
 <rss version="2.0"><channel>
-<title>Blog RSS</title><link>{}</link><description>Feed</description>{}
-</channel></rss>"#, cfg.site_base, items))
+<title>Blog RSS</title><link>{}</link><description>Feed</description>{}{}
+</channel></rss>"#, cfg.site_base, language, items))
 }
 
 pub async fn build_sitemap(db: &Db, cfg: &Config) -> anyhow::Result<String> {
-    let rows = sqlx::query!("SELECT slug, updated_at FROM posts WHERE status='published'")
-        .fetch_all(db).await?;
+    let rows = sqlx::query!(
+        r#"SELECT slug, updated_at, lang as "lang!: String", translation_group
+             FROM posts WHERE status='published' AND visibility='public'"#
+    )
+    .fetch_all(db).await?;
+
+    // 译文组 → [(lang, slug)]，用于输出 hreflang 互链
+    let mut groups: HashMap<&str, Vec<(&str, &str)>> = HashMap::new();
+    for r in &rows {
+        if let Some(g) = r.translation_group.as_deref() {
+            groups.entry(g).or_default().push((r.lang.as_str(), r.slug.as_str()));
+        }
+    }
+
     let mut urls = String::new();
-    for r in rows {
+    for r in &rows {
+        let mut alternates = String::new();
+        if let Some(members) = r.translation_group.as_deref().and_then(|g| groups.get(g))
+            && members.len() > 1
+        {
+            for (lang, slug) in members {
+                alternates.push_str(&format!(
+                  r#"<xhtml:link rel="alternate" hreflang="{}" href="{}/posts/{}"/>"#,
+                  xml_escape(lang), cfg.site_base, slug
+                ));
+            }
+        }
         urls.push_str(&format!(
-          "<url><loc>{}/posts/{}</loc><lastmod>{}</lastmod><changefreq>weekly</changefreq></url>",
-          cfg.site_base, r.slug, r.updated_at
+          "<url><loc>{}/posts/{}</loc><lastmod>{}</lastmod><changefreq>weekly</changefreq>{}</url>",
+          cfg.site_base, r.slug, r.updated_at, alternates
         ));
     }
     Ok(format!(r#"<?xml version="1.0" encoding="UTF-8"?>
-<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">{}</urlset>"#, urls))
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:xhtml="http://www.w3.org/1999/xhtml">{}</urlset>"#, urls))
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
 }
@@ -0,0 +1,263 @@
+// 渲染后 HTML 的白名单清洗：作者 / 导入内容里的 <script>、事件属性、javascript: 链接等一律去掉
+// 标签 / 属性 / URL 协议可通过环境变量扩展；iframe 只保留白名单主机的 https 地址
+use std::collections::HashSet;
+
+use ammonia::{Builder, UrlRelative};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use url::Url;
+
+// 公式渲染（latex2mathml）用到的 MathML 元素与属性
+const MATH_TAGS: &[&str] = &[
+    "math", "mi", "mn", "mo", "ms", "mtext", "mspace", "mrow", "mfrac", "msqrt", "mroot", "mstyle",
+    "mpadded", "mphantom", "menclose", "msub", "msup", "msubsup", "munder", "mover", "munderover",
+    "mmultiscripts", "mprescripts", "none", "mtable", "mtr", "mtd", "semantics", "annotation",
+];
+const MATH_ATTRS: &[&str] = &[
+    "display", "mathvariant", "displaystyle", "scriptlevel", "stretchy", "form", "fence",
+    "separator", "lspace", "rspace", "minsize", "maxsize", "accent", "accentunder", "largeop",
+    "movablelimits", "symmetric", "width", "height", "depth", "linethickness", "columnalign",
+    "rowalign", "notation", "encoding",
+];
+
+const DEFAULT_SCHEMES: &str = "http,https,mailto";
+const DEFAULT_EMBED_HOSTS: &str = "www.youtube.com,www.youtube-nocookie.com,player.vimeo.com";
+
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub extra_tags: Vec<String>,
+    pub extra_attrs: Vec<(String, String)>, // (标签, 属性)，标签为 * 表示所有标签
+    pub url_schemes: Vec<String>,
+    pub embed_hosts: Vec<String>,
+    pub site_host: Option<String>, // 站内链接不加 rel
+}
+
+impl Policy {
+    /// SANITIZE_EXTRA_TAGS=details,summary
+    /// SANITIZE_EXTRA_ATTRS=span:title,*:lang
+    /// SANITIZE_URL_SCHEMES=http,https,mailto（覆盖默认）
+    /// EMBED_HOSTS=www.youtube.com,player.vimeo.com（覆盖默认）
+    pub fn from_env(site_base: &str) -> anyhow::Result<Self> {
+        let extra_attrs = list(&std::env::var("SANITIZE_EXTRA_ATTRS").unwrap_or_default())
+            .into_iter()
+            .map(|pair| match pair.split_once(':') {
+                Some((t, a)) if !t.is_empty() && !a.is_empty() => Ok((t.to_string(), a.to_string())),
+                _ => Err(anyhow::anyhow!("SANITIZE_EXTRA_ATTRS: expected tag:attr, got `{}`", pair)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            extra_tags: list(&std::env::var("SANITIZE_EXTRA_TAGS").unwrap_or_default()),
+            extra_attrs,
+            url_schemes: list(
+                &std::env::var("SANITIZE_URL_SCHEMES").unwrap_or(DEFAULT_SCHEMES.into()),
+            ),
+            embed_hosts: list(&std::env::var("EMBED_HOSTS").unwrap_or(DEFAULT_EMBED_HOSTS.into())),
+            site_host: host_of(site_base),
+        })
+    }
+
+    pub fn clean(&self, html: &str) -> String {
+        let embed_hosts = self.embed_hosts.clone();
+        let mut b = Builder::default();
+        b.add_tags(["iframe", "input", "section"])
+            .add_tags(MATH_TAGS)
+            .add_tags(self.extra_tags.iter().map(String::as_str))
+            .add_tag_attributes("h1", ["id"])
+            .add_tag_attributes("h2", ["id"])
+            .add_tag_attributes("h3", ["id"])
+            .add_tag_attributes("h4", ["id"])
+            .add_tag_attributes("h5", ["id"])
+            .add_tag_attributes("h6", ["id"])
+            .add_tag_attributes("code", ["class"]) // language-xxx
+            .add_tag_attributes("pre", ["class"]) // 代码高亮的 highlight / line / hl-* 类
+            .add_tag_attributes("span", ["class"])
+            .add_tag_attributes("div", ["class"]) // 提示块 markdown-alert-*
+            .add_tag_attributes("p", ["class"])
+            .add_tag_attributes("section", ["class", "data-footnotes"]) // 脚注
+            .add_tag_attributes("sup", ["class"])
+            .add_tag_attributes("blockquote", ["class"]) // 短代码 embed-*
+            .add_tag_attributes("li", ["id"])
+            .add_tag_attributes("img", ["title", "srcset", "sizes", "loading", "decoding"]) // 响应式图片
+            .add_tag_attributes(
+                "a",
+                [
+                    "id",
+                    "class",
+                    "aria-label",
+                    "data-footnote-ref",
+                    "data-footnote-backref",
+                    "data-backref",
+                    "data-wikilink",
+                ],
+            )
+            .add_tag_attributes("input", ["type", "checked", "disabled"]) // 任务列表
+            .add_tag_attributes(
+                "iframe",
+                ["src", "width", "height", "title", "allow", "allowfullscreen", "frameborder", "loading"],
+            )
+            .url_schemes(self.url_schemes.iter().map(String::as_str).collect::<HashSet<_>>())
+            .url_relative(UrlRelative::PassThrough)
+            .link_rel(None) // rel 在下面按站内 / 站外单独处理
+            .attribute_filter(move |element, attribute, value| match (element, attribute) {
+                ("input", "type") => (value == "checkbox").then_some(value.into()),
+                ("iframe", "src") => embed_src(value, &embed_hosts).then_some(value.into()),
+                ("img", "srcset") => safe_srcset(value).then_some(value.into()),
+                _ => Some(value.into()),
+            });
+        for tag in MATH_TAGS {
+            b.add_tag_attributes(tag, MATH_ATTRS);
+        }
+        for (tag, attr) in &self.extra_attrs {
+            if tag == "*" {
+                b.add_generic_attributes([attr.as_str()]);
+            } else {
+                b.add_tag_attributes(tag.as_str(), [attr.as_str()]);
+            }
+        }
+
+        let html = b.clean(html).to_string();
+        // src 被过滤掉的 iframe 整个去掉
+        let html = RE_IFRAME.replace_all(&html, |c: &Captures| {
+            if c[1].contains(" src=") { c[0].to_string() } else { String::new() }
+        });
+        // 站外链接加 rel="noopener nofollow"
+        RE_LINK
+            .replace_all(&html, |c: &Captures| {
+                let external = host_of(&c[2]).is_some_and(|h| Some(&h) != self.site_host.as_ref());
+                if external {
+                    format!("{} rel=\"noopener nofollow\"", &c[0])
+                } else {
+                    c[0].to_string()
+                }
+            })
+            .into_owned()
+    }
+}
+
+// ammonia 输出是规范化的：属性值一律双引号、& 已转义
+static RE_IFRAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<iframe\b([^>]*)>.*?</iframe>").unwrap());
+static RE_LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<a\b([^>]*?)\shref="([^"]*)""#).unwrap());
+
+// 解析相对地址用的占位站点（.invalid 不会是真实主机）
+const PLACEHOLDER_HOST: &str = "base.invalid";
+static PLACEHOLDER: Lazy<Url> =
+    Lazy::new(|| Url::parse(&format!("https://{}/", PLACEHOLDER_HOST)).unwrap());
+
+/// 链接指向的主机名：按浏览器的规则解析（\ 当作 /、/\host 是协议相对地址…），站内相对地址返回 None
+fn host_of(url: &str) -> Option<String> {
+    let parsed = PLACEHOLDER.join(url).ok()?;
+    let host = parsed.host_str()?;
+    (host != PLACEHOLDER_HOST).then(|| host.to_lowercase())
+}
+
+/// iframe 只嵌 https 的白名单主机，不带账号；含 \、空白或控制字符的地址一律拒绝，免得浏览器和这里理解不一致
+fn embed_src(value: &str, hosts: &[String]) -> bool {
+    if value.chars().any(|c| c == '\\' || c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    let Ok(url) = Url::parse(value) else { return false };
+    url.scheme() == "https"
+        && url.username().is_empty()
+        && url.password().is_none()
+        && url.host_str().is_some_and(|h| hosts.iter().any(|e| e == h))
+}
+
+/// srcset 里每个地址都得是站内路径或 http(s)（ammonia 不检查 srcset 里的协议）
+fn safe_srcset(value: &str) -> bool {
+    value.split(',').all(|candidate| {
+        let url = candidate.split_whitespace().next().unwrap_or("");
+        (url.starts_with('/') && !url.starts_with("//"))
+            || url.starts_with("https://")
+            || url.starts_with("http://")
+    })
+}
+
+fn list(s: &str) -> Vec<String> {
+    s.split(',').map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        Policy {
+            extra_tags: vec![],
+            extra_attrs: vec![],
+            url_schemes: list(DEFAULT_SCHEMES),
+            embed_hosts: list(DEFAULT_EMBED_HOSTS),
+            site_host: Some("blog.example.com".into()),
+        }
+    }
+
+    #[test]
+    fn strips_script_schemes() {
+        let p = policy();
+        for href in ["javascript:alert(1)", "JaVaScRiPt:alert(1)", "data:text/html,<script>x</script>"] {
+            let out = p.clean(&format!("<a href=\"{}\">x</a>", href));
+            assert_eq!(out, "<a>x</a>", "{}", href);
+        }
+        let out = p.clean("<p onclick=\"x()\">a<script>alert(1)</script></p>");
+        assert_eq!(out, "<p>a</p>");
+        let out = p.clean("<img src=\"data:image/svg+xml,x\" alt=\"a\">");
+        assert_eq!(out, "<img alt=\"a\">");
+    }
+
+    #[test]
+    fn embed_hosts_only() {
+        let hosts = list(DEFAULT_EMBED_HOSTS);
+        assert!(embed_src("https://www.youtube-nocookie.com/embed/abc", &hosts));
+        assert!(embed_src("https://player.vimeo.com/video/1", &hosts));
+        for bad in [
+            "http://www.youtube.com/embed/abc",
+            "https://evil.example/embed",
+            "https://www.youtube.com.evil.example/",
+            "https://user@www.youtube.com/embed/abc",
+            "https://u:p@www.youtube.com/embed/abc",
+            "https://evil.example\\@www.youtube.com/",
+            "https:\\\\www.youtube.com/embed/abc",
+            "https://www.youtube.com/embed/a b",
+            "//www.youtube.com/embed/abc",
+            "javascript:alert(1)",
+        ] {
+            assert!(!embed_src(bad, &hosts), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn drops_iframe_without_src() {
+        let p = policy();
+        let ok = "<iframe src=\"https://www.youtube.com/embed/abc\"></iframe>";
+        assert_eq!(p.clean(ok), ok);
+        assert_eq!(p.clean("a<iframe src=\"https://evil.example/x\">fallback</iframe>b"), "ab");
+        assert_eq!(p.clean("a<iframe src=\"javascript:alert(1)\"></iframe>b"), "ab");
+    }
+
+    #[test]
+    fn srcset_schemes() {
+        assert!(safe_srcset("/uploads/a-480w.webp 480w, /uploads/a.png 1200w"));
+        assert!(safe_srcset("https://cdn.example/a.png 2x"));
+        assert!(!safe_srcset("/uploads/a.png 1x, javascript:alert(1) 2x"));
+        assert!(!safe_srcset("//evil.example/a.png 1x"));
+        assert!(!safe_srcset("data:image/png;base64,AAAA 1x"));
+        let out = policy().clean("<img src=\"/a.png\" srcset=\"/a.png 1x, data:x 2x\">");
+        assert_eq!(out, "<img src=\"/a.png\">");
+    }
+
+    #[test]
+    fn rel_only_on_external_links() {
+        let p = policy();
+        assert_eq!(p.clean("<a href=\"/posts/a\">x</a>"), "<a href=\"/posts/a\">x</a>");
+        assert_eq!(p.clean("<a href=\"#fn-1\">x</a>"), "<a href=\"#fn-1\">x</a>");
+        let own = "<a href=\"https://Blog.Example.com/posts/a\">x</a>";
+        assert_eq!(p.clean(own), own);
+        for href in ["https://other.example/", "//other.example/", "/\\other.example/"] {
+            let out = p.clean(&format!("<a href=\"{}\">x</a>", href));
+            assert!(out.contains(" rel=\"noopener nofollow\""), "{}", out);
+        }
+        // 作者自己写的 rel 不保留
+        let out = p.clean("<a href=\"https://other.example/\" rel=\"me\">x</a>");
+        assert_eq!(out, "<a href=\"https://other.example/\" rel=\"noopener nofollow\">x</a>");
+    }
+}
@@ -0,0 +1,543 @@
+// 短代码：正文里的 {{< youtube id >}}、{{< callout warning >}}…{{< /callout >}} 在 Markdown 解析前展开成 HTML
+// 内置 youtube / vimeo / gist / tweet / callout；SHORTCODE_DIR 下的 <name>.html（minijinja 模板）注册为自定义短代码，
+// 同名时覆盖内置。代码块和行内代码里的不展开；{{</* x */>}} 原样输出 {{< x >}}
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    ops::Range,
+    path::Path,
+    sync::Arc,
+};
+
+use minijinja::{context, Environment};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+const BUILTIN: &[&str] = &["youtube", "vimeo", "gist", "tweet", "callout"];
+
+/// 可用的短代码（内置 + 模板目录）
+#[derive(Clone, Default)]
+pub struct Registry {
+    templates: Option<Arc<Environment<'static>>>,
+    custom: Vec<String>,
+}
+
+impl Registry {
+    /// SHORTCODE_DIR=./shortcodes（可选）
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("SHORTCODE_DIR") {
+            Ok(dir) if !dir.is_empty() => Self::load(Path::new(&dir)),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// 目录下每个 <name>.html 是一个短代码模板
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let mut env = Environment::new();
+        let mut custom = vec![];
+        let entries = fs::read_dir(dir)
+            .map_err(|e| anyhow::anyhow!("SHORTCODE_DIR {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry?.path();
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+            if path.extension().is_none_or(|e| e != "html") || !RE_NAME.is_match(name) {
+                continue;
+            }
+            env.add_template_owned(format!("{}.html", name), fs::read_to_string(&path)?)
+                .map_err(|e| anyhow::anyhow!("shortcode {}: {}", path.display(), e))?;
+            custom.push(name.to_string());
+        }
+        custom.sort();
+        Ok(Self { templates: Some(Arc::new(env)), custom })
+    }
+
+    /// 全部可用名字
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = BUILTIN.to_vec();
+        names.extend(self.custom.iter().map(String::as_str).filter(|n| !BUILTIN.contains(n)));
+        names
+    }
+
+    fn call(&self, name: &str, args: &Args, inner: Option<&str>) -> Result<String, String> {
+        if let Some(env) = self.templates.as_ref().filter(|_| self.custom.iter().any(|c| c == name)) {
+            return env
+                .get_template(&format!("{}.html", name))
+                .and_then(|t| {
+                    t.render(context! {
+                        args => &args.positional,
+                        params => &args.named,
+                        inner => inner,
+                    })
+                })
+                .map_err(|e| e.to_string());
+        }
+        match name {
+            "youtube" => youtube(args),
+            "vimeo" => vimeo(args),
+            "gist" => gist(args),
+            "tweet" => tweet(args),
+            "callout" => callout(args, inner),
+            _ => Err(format!("unknown shortcode `{}`", name)),
+        }
+    }
+}
+
+/// 参数：位置参数与 key=value（值可加双引号）
+#[derive(Default)]
+struct Args {
+    positional: Vec<String>,
+    named: BTreeMap<String, String>,
+}
+
+impl Args {
+    fn parse(s: &str) -> Self {
+        let mut args = Args::default();
+        for c in RE_ARG.captures_iter(s) {
+            let value = c.get(2).or(c.get(3)).map_or("", |m| m.as_str()).to_string();
+            match c.get(1) {
+                Some(key) => {
+                    args.named.insert(key.as_str().to_string(), value);
+                }
+                None => args.positional.push(value),
+            }
+        }
+        args
+    }
+
+    /// 命名参数优先，否则取第 pos 个位置参数
+    fn get(&self, key: &str, pos: usize) -> Option<&str> {
+        self.named.get(key).or(self.positional.get(pos)).map(String::as_str)
+    }
+
+    fn require(&self, key: &str, pos: usize, re: &Regex) -> Result<&str, String> {
+        match self.get(key, pos) {
+            Some(v) if re.is_match(v) => Ok(v),
+            Some(v) => Err(format!("invalid {} `{}`", key, v)),
+            None => Err(format!("missing {}", key)),
+        }
+    }
+}
+
+static RE_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Za-z][\w-]*$").unwrap());
+// {{< name args >}}、{{< /name >}}、{{< name args />}}
+static RE_TAG: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"\{\{<\s*(/)?\s*([A-Za-z][\w-]*)((?:\s+(?:[A-Za-z][\w-]*=)?(?:"[^"]*"|[^\s">/][^\s">]*))*)\s*(/)?\s*>\}\}"#,
+    )
+    .unwrap()
+});
+static RE_ESCAPED: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)\{\{</\*(.*?)\*/>\}\}").unwrap());
+static RE_ARG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?:([A-Za-z][\w-]*)=)?(?:"([^"]*)"|([^\s"]+))"#).unwrap());
+static RE_FENCE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^ {0,3}(`{3,}|~{3,})").unwrap());
+
+static RE_YOUTUBE_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Za-z0-9_-]{11}$").unwrap());
+static RE_DIGITS: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d{1,20}$").unwrap());
+static RE_USER: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Za-z0-9_-]{1,39}$").unwrap());
+static RE_GIST_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[0-9a-f]{1,40}$").unwrap());
+static RE_KIND: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?i)(note|tip|important|warning|caution)$").unwrap());
+
+// 成对短代码最多嵌套几层（inner 递归展开）
+const MAX_DEPTH: usize = 16;
+
+/// 展开正文里的短代码；出错的原样显示并带上错误信息（不影响保存）
+/// 标签只扫描一遍，开始标签与其后第一个同名结束标签配对（不支持同名嵌套），整体是线性的
+pub fn expand(md: &str, reg: &Registry) -> String {
+    let tokens = tokenize(md);
+    // 每个开始标签之后第一个同名结束标签
+    let mut closes = vec![None; tokens.len()];
+    let mut next: HashMap<&str, usize> = HashMap::new();
+    for (i, t) in tokens.iter().enumerate().rev() {
+        match t.kind {
+            Kind::Close { name } => {
+                next.insert(name, i);
+            }
+            Kind::Open { name, self_closing: false, .. } => closes[i] = next.get(name).copied(),
+            _ => {}
+        }
+    }
+    let doc = Doc { md, reg, tokens, closes };
+    let mut out = String::with_capacity(md.len());
+    doc.render(0, md.len(), 0..doc.tokens.len(), 0, &mut out);
+    out
+}
+
+/// 代码以外的一个标签（或转义写法）
+struct Token<'a> {
+    start: usize,
+    end: usize,
+    kind: Kind<'a>,
+}
+
+enum Kind<'a> {
+    Escaped(&'a str),
+    Open { name: &'a str, args: &'a str, self_closing: bool },
+    Close { name: &'a str },
+}
+
+/// 某个正则从 pos 起的下一个匹配：被越过了才重新查找，整篇只扫一遍
+struct NextMatch<'h> {
+    re: &'static Regex,
+    hay: &'h str,
+    cur: Option<Captures<'h>>,
+}
+
+impl<'h> NextMatch<'h> {
+    fn new(re: &'static Regex, hay: &'h str) -> Self {
+        Self { re, hay, cur: re.captures(hay) }
+    }
+
+    fn at(&mut self, pos: usize) -> Option<&Captures<'h>> {
+        if self.cur.as_ref().is_some_and(|c| c.get(0).unwrap().start() < pos) {
+            self.cur = self.re.captures_at(self.hay, pos);
+        }
+        self.cur.as_ref()
+    }
+}
+
+fn tokenize(md: &str) -> Vec<Token<'_>> {
+    let code = code_ranges(md);
+    let (mut tags, mut escaped) = (NextMatch::new(&RE_TAG, md), NextMatch::new(&RE_ESCAPED, md));
+    let mut tokens = vec![];
+    let mut pos = 0;
+    loop {
+        // 短代码与转义写法取先出现的
+        let t = tags.at(pos).map(|c| c.get(0).unwrap().range());
+        let e = escaped.at(pos).map(|c| c.get(0).unwrap().range());
+        let (range, is_escaped) = match (t, e) {
+            (Some(t), Some(e)) if e.start < t.start => (e, true),
+            (Some(t), _) => (t, false),
+            (None, Some(e)) => (e, true),
+            (None, None) => break,
+        };
+        pos = range.end;
+        if in_code(&code, range.start) {
+            continue;
+        }
+        let kind = if is_escaped {
+            Kind::Escaped(escaped.cur.as_ref().unwrap().get(1).unwrap().as_str())
+        } else {
+            let c = tags.cur.as_ref().unwrap();
+            let name = c.get(2).unwrap().as_str();
+            match c.get(1) {
+                Some(_) => Kind::Close { name },
+                None => Kind::Open {
+                    name,
+                    args: c.get(3).map_or("", |m| m.as_str()),
+                    self_closing: c.get(4).is_some(),
+                },
+            }
+        };
+        tokens.push(Token { start: range.start, end: range.end, kind });
+    }
+    tokens
+}
+
+struct Doc<'a> {
+    md: &'a str,
+    reg: &'a Registry,
+    tokens: Vec<Token<'a>>,
+    closes: Vec<Option<usize>>,
+}
+
+impl Doc<'_> {
+    /// 输出 md[from..to]，其中的标签为 tokens[toks]
+    fn render(&self, from: usize, to: usize, toks: Range<usize>, depth: usize, out: &mut String) {
+        let mut pos = from;
+        let mut i = toks.start;
+        while i < toks.end {
+            let t = &self.tokens[i];
+            out.push_str(&self.md[pos..t.start]);
+            pos = t.end;
+            let src = &self.md[t.start..t.end];
+            let close = self.closes[i].filter(|j| *j < toks.end);
+            i += 1;
+            let (name, args) = match t.kind {
+                Kind::Escaped(inner) => {
+                    out.push_str(&format!("{{{{<{}>}}}}", inner));
+                    continue;
+                }
+                Kind::Close { .. } => {
+                    out.push_str(&error(src, "closing tag without opening tag"));
+                    continue;
+                }
+                Kind::Open { name, args, .. } => (name, args),
+            };
+
+            let mut inner = None;
+            if let Some(j) = close {
+                let end = &self.tokens[j];
+                let first = i;
+                (pos, i) = (end.end, j + 1);
+                if depth >= MAX_DEPTH {
+                    out.push_str(&error(
+                        &self.md[t.start..end.end],
+                        "shortcodes are nested too deeply",
+                    ));
+                    continue;
+                }
+                let mut s = String::new();
+                self.render(t.end, end.start, first..j, depth + 1, &mut s);
+                inner = Some(s);
+            }
+            match self.reg.call(name, &Args::parse(args), inner.as_deref()) {
+                Ok(html) => out.push_str(&html),
+                Err(e) => out.push_str(&error(src, &e)),
+            }
+        }
+        out.push_str(&self.md[pos..to]);
+    }
+}
+
+/// ranges 按起点排序、互不重叠
+fn in_code(ranges: &[(usize, usize)], i: usize) -> bool {
+    let n = ranges.partition_point(|(a, _)| *a <= i);
+    n > 0 && i < ranges[n - 1].1
+}
+
+/// 围栏代码块与行内代码的字节区间
+fn code_ranges(md: &str) -> Vec<(usize, usize)> {
+    let mut ranges = vec![];
+    let mut fence: Option<(usize, char, usize)> = None; // (起点, 字符, 长度)
+    let mut prose_start = 0;
+    let mut offset = 0;
+    for line in md.split_inclusive('\n') {
+        let marker = RE_FENCE.captures(line).map(|c| c[1].to_string());
+        match (&fence, marker) {
+            (None, Some(m)) => {
+                inline_code(&md[prose_start..offset], prose_start, &mut ranges);
+                fence = Some((offset, m.chars().next().unwrap(), m.len()));
+            }
+            (Some((start, ch, len)), Some(m))
+                if m.starts_with(*ch) && m.len() >= *len && line.trim().len() == m.len() =>
+            {
+                ranges.push((*start, offset + line.len()));
+                fence = None;
+                prose_start = offset + line.len();
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+    match fence {
+        Some((start, ..)) => ranges.push((start, md.len())), // 没闭合的围栏延续到结尾
+        None => inline_code(&md[prose_start..], prose_start, &mut ranges),
+    }
+    ranges
+}
+
+/// `code`：同样长度的反引号串配对
+fn inline_code(text: &str, base: usize, ranges: &mut Vec<(usize, usize)>) {
+    let runs: Vec<(usize, usize)> = {
+        let b = text.as_bytes();
+        let mut runs = vec![];
+        let mut i = 0;
+        while i < b.len() {
+            if b[i] == b'`' {
+                let start = i;
+                while i < b.len() && b[i] == b'`' {
+                    i += 1;
+                }
+                runs.push((start, i - start));
+            } else {
+                i += 1;
+            }
+        }
+        runs
+    };
+    // 每个反引号串之后第一个同样长度的串（倒着扫一遍）
+    let mut next_same = vec![None; runs.len()];
+    let mut last: HashMap<usize, usize> = HashMap::new();
+    for (k, (_, len)) in runs.iter().enumerate().rev() {
+        next_same[k] = last.insert(*len, k);
+    }
+    let mut i = 0;
+    while i < runs.len() {
+        let (start, len) = runs[i];
+        match next_same[i] {
+            Some(j) => {
+                let (end, _) = runs[j];
+                ranges.push((base + start, base + end + len));
+                i = j + 1;
+            }
+            None => i += 1,
+        }
+    }
+}
+
+fn error(src: &str, msg: &str) -> String {
+    format!("<code class=\"shortcode-error\" title=\"{}\">{}</code>", escape(msg), escape(src))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// 内置短代码：视频用不写 cookie 的播放器并延迟加载；gist / tweet 不引入第三方脚本，只输出链接卡片
+
+fn youtube(args: &Args) -> Result<String, String> {
+    let id = args.require("id", 0, &RE_YOUTUBE_ID)?;
+    let start = match args.get("start", 1) {
+        Some(s) if RE_DIGITS.is_match(s) => format!("?start={}", s),
+        Some(s) => return Err(format!("invalid start `{}`", s)),
+        None => String::new(),
+    };
+    Ok(format!(
+        "<div class=\"embed embed-youtube\"><iframe src=\"https://www.youtube-nocookie.com/embed/{}{}\" title=\"{}\" loading=\"lazy\" allow=\"encrypted-media; picture-in-picture\" allowfullscreen></iframe></div>",
+        id,
+        start,
+        escape(args.named.get("title").map_or("YouTube video", String::as_str))
+    ))
+}
+
+fn vimeo(args: &Args) -> Result<String, String> {
+    let id = args.require("id", 0, &RE_DIGITS)?;
+    Ok(format!(
+        "<div class=\"embed embed-vimeo\"><iframe src=\"https://player.vimeo.com/video/{}?dnt=1\" title=\"{}\" loading=\"lazy\" allow=\"fullscreen; picture-in-picture\" allowfullscreen></iframe></div>",
+        id,
+        escape(args.named.get("title").map_or("Vimeo video", String::as_str))
+    ))
+}
+
+fn gist(args: &Args) -> Result<String, String> {
+    let user = args.require("user", 0, &RE_USER)?;
+    let id = args.require("id", 1, &RE_GIST_ID)?;
+    let file = args.get("file", 2);
+    let url = match file {
+        Some(f) => format!("https://gist.github.com/{}/{}#file-{}", user, id, escape(&f.replace('.', "-"))),
+        None => format!("https://gist.github.com/{}/{}", user, id),
+    };
+    Ok(format!(
+        "<div class=\"embed embed-gist\"><a href=\"{}\">{} — gist by {} on GitHub</a></div>",
+        url,
+        escape(file.unwrap_or(id)),
+        user
+    ))
+}
+
+fn tweet(args: &Args) -> Result<String, String> {
+    let user = args.require("user", 0, &RE_USER)?;
+    let id = args.require("id", 1, &RE_DIGITS)?;
+    Ok(format!(
+        "<blockquote class=\"embed embed-tweet\"><p><a href=\"https://x.com/{}/status/{}\">Post by @{} on X</a></p></blockquote>",
+        user, id, user
+    ))
+}
+
+/// 与 > [!NOTE] 提示块同样的结构；内容仍按 Markdown 渲染（前后留空行）
+fn callout(args: &Args, inner: Option<&str>) -> Result<String, String> {
+    let kind = args.get("type", 0).unwrap_or("note");
+    if !RE_KIND.is_match(kind) {
+        return Err(format!("invalid type `{}`", kind));
+    }
+    let kind = kind.to_lowercase();
+    let title = match args.get("title", 1) {
+        Some(t) => escape(t),
+        None => {
+            let mut t = kind.clone();
+            t[..1].make_ascii_uppercase();
+            t
+        }
+    };
+    Ok(format!(
+        "<div class=\"markdown-alert markdown-alert-{}\">\n<p class=\"markdown-alert-title\">{}</p>\n\n{}\n\n</div>\n",
+        kind,
+        title,
+        inner.unwrap_or_default().trim()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(md: &str) -> String {
+        expand(md, &Registry::default())
+    }
+
+    #[test]
+    fn expands_self_closing_and_escaped() {
+        let out = run("a {{< youtube dQw4w9WgXcQ >}} b {{</* youtube x */>}} c");
+        assert!(out.starts_with("a <div class=\"embed embed-youtube\">"));
+        assert!(out.contains("/embed/dQw4w9WgXcQ\""));
+        assert!(out.ends_with(" b {{< youtube x >}} c"));
+    }
+
+    #[test]
+    fn skips_code() {
+        let md = "`{{< youtube a >}}`\n\n```\n{{< youtube b >}}\n```\n`` {{< nope >}} ``";
+        assert_eq!(run(md), md);
+    }
+
+    #[test]
+    fn pairs_and_nests() {
+        let out = run("{{< callout warning >}}x {{< youtube dQw4w9WgXcQ >}}{{< /callout >}} y");
+        assert!(out.starts_with("<div class=\"markdown-alert markdown-alert-warning\">"));
+        assert!(out.contains("x <div class=\"embed embed-youtube\">"));
+        assert!(out.ends_with("</div>\n y"));
+    }
+
+    #[test]
+    fn unclosed_and_stray_tags() {
+        // 没有结束标签的按单标签处理
+        let out = run("{{< callout >}} text");
+        assert!(out.starts_with("<div class=\"markdown-alert markdown-alert-note\">"));
+        assert!(out.ends_with(" text"));
+        assert!(run("a {{< /callout >}}").contains("closing tag without opening tag"));
+        assert!(run("{{< nope >}}").contains("unknown shortcode `nope`"));
+    }
+
+    #[test]
+    fn same_name_pairs_with_first_closing() {
+        let out = run("{{< callout >}}a{{< callout >}}b{{< /callout >}}c{{< /callout >}}");
+        assert_eq!(out.matches("markdown-alert-note").count(), 2);
+        assert!(out.contains("closing tag without opening tag"));
+    }
+
+    #[test]
+    fn caps_nesting_depth() {
+        let mut env = Environment::new();
+        let mut custom = vec![];
+        for i in 0..=MAX_DEPTH {
+            env.add_template_owned(format!("n{}.html", i), "[{{ inner|safe }}]").unwrap();
+            custom.push(format!("n{}", i));
+        }
+        let reg = Registry { templates: Some(Arc::new(env)), custom };
+        let nest = |depth: usize| {
+            let open: String = (0..depth).map(|i| format!("{{{{< n{} >}}}}", i)).collect();
+            let close: String = (0..depth).rev().map(|i| format!("{{{{< /n{} >}}}}", i)).collect();
+            format!("{}x{}", open, close)
+        };
+        let ok = expand(&nest(MAX_DEPTH), &reg);
+        assert_eq!(ok, format!("{}x{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH)));
+        assert!(expand(&nest(MAX_DEPTH + 1), &reg).contains("shortcodes are nested too deeply"));
+    }
+
+    #[test]
+    fn many_unclosed_tags_stay_linear() {
+        let md = "{{< callout >}} ` ".repeat(20_000);
+        let started = std::time::Instant::now();
+        let out = run(&md);
+        // 反引号两两配成行内代码，一半的标签在代码里
+        assert_eq!(out.matches("markdown-alert-note").count(), 10_000);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn code_ranges_fences_and_inline() {
+        let md = "a `b` c ``d ` e`` f\n~~~\ncode\n~~~~\n`g\n";
+        let fence = md.find("~~~").unwrap();
+        let after = md.find("`g").unwrap();
+        assert_eq!(code_ranges(md), vec![(2, 5), (8, 17), (fence, after)]);
+        // 没闭合的围栏延续到结尾；长度不同的反引号串不配对
+        assert_eq!(code_ranges("x\n```\ny"), vec![(2, 7)]);
+        assert_eq!(code_ranges("`a``"), vec![]);
+    }
+
+    #[test]
+    fn in_code_binary_search() {
+        let ranges = [(2, 5), (8, 10)];
+        let hits: Vec<usize> = (0..12).filter(|i| in_code(&ranges, *i)).collect();
+        assert_eq!(hits, vec![2, 3, 4, 8, 9]);
+    }
+}
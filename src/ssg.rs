@@ -0,0 +1,265 @@
+// 静态站生成：已发布的 public 文章、标签页、分页首页、rss.xml、sitemap.xml → 一个静态 HTML 目录（用于镜像托管）
+// 查询复用 routes::public 与 rss；文章页按页面数据的哈希增量重建，列表页和订阅每次全量生成（很便宜）
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use minijinja::{context, Environment};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::Config,
+    db::Db,
+    highlight,
+    markdown,
+    routes::public::{self, ListParams},
+    rss,
+    storage,
+};
+
+// 构建清单：记录每篇文章上次渲染时页面数据的哈希，以及模板 / 站点设置的指纹
+const MANIFEST: &str = ".build-manifest.json";
+
+// 内置模板；--templates 目录里的同名文件优先
+const TEMPLATES: &[(&str, &str)] = &[
+    ("base.html", include_str!("../templates/site/base.html")),
+    ("index.html", include_str!("../templates/site/index.html")),
+    ("post.html", include_str!("../templates/site/post.html")),
+];
+
+pub struct Options {
+    pub out: PathBuf,
+    pub templates: Option<PathBuf>,
+    pub title: String,
+    pub page_size: i64,
+    pub full: bool, // 忽略清单，全部重建
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub rendered: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub list_pages: usize,
+    pub uploads: usize,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    fingerprint: String,
+    posts: BTreeMap<String, String>, // slug → post_json 的 sha256
+}
+
+pub async fn build(db: &Db, cfg: &Config, opts: &Options) -> anyhow::Result<Report> {
+    let mut report = Report::default();
+    fs::create_dir_all(&opts.out)?;
+
+    // 1) 模板
+    let mut env = Environment::new();
+    let mut hasher = Sha256::new();
+    for (name, builtin) in TEMPLATES {
+        let source = match &opts.templates {
+            Some(dir) if dir.join(name).is_file() => fs::read_to_string(dir.join(name))?,
+            _ => builtin.to_string(),
+        };
+        hasher.update(name.as_bytes());
+        hasher.update(source.as_bytes());
+        env.add_template_owned(*name, source)?;
+    }
+    hasher.update(format!(
+        "{}\n{}\n{}\n{}\n{}",
+        cfg.site_base,
+        opts.title,
+        cfg.default_lang,
+        cfg.highlight_theme,
+        markdown::RENDERER_VERSION
+    ));
+    let fingerprint = format!("{:x}", hasher.finalize());
+
+    let manifest_path = opts.out.join(MANIFEST);
+    let old: Manifest = fs::read(&manifest_path)
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .unwrap_or_default();
+    let full = opts.full || old.fingerprint != fingerprint;
+
+    let site = context! {
+        title => &opts.title,
+        base => &cfg.site_base,
+        lang => &cfg.default_lang,
+    };
+
+    // 2) 文章页：只重建页面数据变了的
+    // 反向链接、译文、标签和重新渲染的正文都不改本文的 updated_at，所以比的是整份 post_json
+    let posts = sqlx::query!(
+        "SELECT slug FROM posts WHERE status = 'published' AND visibility = 'public'"
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut manifest = Manifest { fingerprint, posts: BTreeMap::new() };
+    for p in &posts {
+        // slug 直接做目录名，跳过会逃出输出目录的
+        if p.slug.is_empty() || p.slug.contains(['/', '\\']) || p.slug.starts_with('.') {
+            continue;
+        }
+        let file = opts.out.join("posts").join(&p.slug).join("index.html");
+        let Some(row) = public::load_post(db, &p.slug, None, false).await? else { continue };
+        let translations = public::translations(db, &row).await?;
+        let tags = public::post_tags(db, &row.id).await?;
+        let backlinks = public::backlinks(db, &row).await?;
+        let post = public::post_json(row, translations, tags, backlinks, false);
+        let hash = format!("{:x}", Sha256::digest(serde_json::to_vec(&post)?));
+        if !full && old.posts.get(&p.slug) == Some(&hash) && file.is_file() {
+            report.unchanged += 1;
+            manifest.posts.insert(p.slug.clone(), hash);
+            continue;
+        }
+        let html = env.get_template("post.html")?.render(context! {
+            site => &site,
+            lang => post["lang"].as_str(),
+            post => &post,
+        })?;
+        write(&file, html.as_bytes())?;
+        report.rendered += 1;
+        manifest.posts.insert(p.slug.clone(), hash);
+    }
+
+    // 已下线（删掉 / 转为草稿或非 public）的文章
+    for slug in old.posts.keys().filter(|s| !manifest.posts.contains_key(*s)) {
+        let dir = opts.out.join("posts").join(slug);
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir)?;
+        }
+        report.removed += 1;
+    }
+
+    // 3) 首页与标签页（整体重建，先清掉旧的分页目录）
+    for dir in ["page", "tags"] {
+        let d = opts.out.join(dir);
+        if d.is_dir() {
+            fs::remove_dir_all(&d)?;
+        }
+    }
+    let size = ListParams { page_size: Some(opts.page_size), ..Default::default() }.size();
+    let home = Listing {
+        tag: None,
+        total: posts.len() as i64,
+        dir: opts.out.clone(),
+        base: cfg.site_base.clone(),
+    };
+    report.list_pages += render_list(db, &env, &site, size, &home).await?;
+
+    for tag in public::tag_counts(db).await? {
+        let count = tag["count"].as_i64().unwrap_or(0);
+        if count == 0 {
+            continue;
+        }
+        let seg = tag["slug"]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .or(tag["id"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        let listing = Listing {
+            tag: Some(&tag),
+            total: count,
+            dir: opts.out.join("tags").join(&seg),
+            base: format!("{}/tags/{}", cfg.site_base, seg),
+        };
+        report.list_pages += render_list(db, &env, &site, size, &listing).await?;
+    }
+
+    // 4) 订阅、站点地图与代码高亮样式
+    let css = highlight::css(&cfg.highlight_theme).unwrap_or_default();
+    write(&opts.out.join("highlight.css"), css.as_bytes())?;
+    write(&opts.out.join("rss.xml"), rss::build_rss(db, cfg, None).await?.as_bytes())?;
+    write(&opts.out.join("sitemap.xml"), rss::build_sitemap(db, cfg).await?.as_bytes())?;
+
+    // 5) 上传文件：缺失或大小不同才复制
+    report.uploads = sync_uploads(cfg, &opts.out.join("uploads")).await?;
+
+    fs::write(&manifest_path, serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(report)
+}
+
+/// 一组分页列表：首页或某个标签
+struct Listing<'a> {
+    tag: Option<&'a Value>,
+    total: i64,
+    dir: PathBuf, // 输出目录
+    base: String, // 对应的站点 URL
+}
+
+/// 渲染 dir/index.html、dir/page/N/index.html；返回页数
+async fn render_list(
+    db: &Db,
+    env: &Environment<'_>,
+    site: &minijinja::Value,
+    size: i64,
+    l: &Listing<'_>,
+) -> anyhow::Result<usize> {
+    let pages = ((l.total + size - 1) / size).max(1);
+    let url = |n: i64| {
+        if n == 1 { format!("{}/", l.base) } else { format!("{}/page/{}/", l.base, n) }
+    };
+
+    for n in 1..=pages {
+        let params = ListParams {
+            page: Some(n),
+            page_size: Some(size),
+            tag: l.tag.and_then(|t| t["name"].as_str()).map(String::from),
+            ..Default::default()
+        };
+        let items = public::list_items(db, &params).await?;
+        let html = env.get_template("index.html")?.render(context! {
+            site => site,
+            tag => l.tag,
+            posts => items,
+            pager => context! {
+                page => n,
+                pages => pages,
+                prev => (n > 1).then(|| url(n - 1)),
+                next => (n < pages).then(|| url(n + 1)),
+            },
+        })?;
+        let file = if n == 1 {
+            l.dir.join("index.html")
+        } else {
+            l.dir.join("page").join(n.to_string()).join("index.html")
+        };
+        write(&file, html.as_bytes())?;
+    }
+    Ok(pages as usize)
+}
+
+fn write(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)?;
+    Ok(())
+}
+
+/// 把存储里的上传文件同步到 dest（本地目录或对象存储都一样）
+async fn sync_uploads(cfg: &Config, dest: &Path) -> anyhow::Result<usize> {
+    let mut copied = 0;
+    for (key, size) in cfg.storage.list("").await? {
+        // 桶里可能有别的程序放进来的怪名字，不让它写出 dest
+        if storage::key(&key).is_none() {
+            continue;
+        }
+        let to = dest.join(&key);
+        if fs::metadata(&to).map(|m| m.len()).ok() == Some(size) {
+            continue;
+        }
+        let Some(data) = cfg.storage.get(&key).await? else { continue };
+        write(&to, &data)?;
+        copied += 1;
+    }
+    Ok(copied)
+}
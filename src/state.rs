@@ -1,8 +1,12 @@
-use crate::{db::Db, config::Config};
+use std::sync::{Arc, Mutex};
+
+use crate::{db::Db, config::Config, rerender, throttle::Throttle};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Db,
     pub cfg: Config,
     pub jwt_secret: String,
+    pub rerender: Arc<Mutex<rerender::Job>>, // 后台重新渲染任务的进度
+    pub unlock_throttle: Arc<Throttle>,       // protected 文章密码尝试（按文章 + IP）
 }
\ No newline at end of file
@@ -0,0 +1,98 @@
+// 单元测试共用：内存数据库（已跑完迁移）、不读环境变量的配置、建用户 / 文章的快捷函数
+use std::sync::Arc;
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use crate::{
+    config::Config,
+    db::{self, Db},
+    error::AppError,
+    images, markdown, media,
+    models::{new_id, now, PostInput},
+    posts::{self, Timestamps},
+    sanitize, shortcodes,
+    state::AppState,
+    storage,
+};
+
+pub const SITE_BASE: &str = "https://blog.example.com";
+
+/// 每次调用都是一个全新的库；只开一个连接且不回收，否则内存库会随连接一起消失
+pub async fn db() -> Db {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    db::migrate(&pool).await.unwrap();
+    pool
+}
+
+pub fn config() -> Config {
+    let upload_dir = std::env::temp_dir().join(format!("blog-test-{}", new_id()));
+    let upload_dir = upload_dir.to_string_lossy().into_owned();
+    Config {
+        bind: "127.0.0.1:0".into(),
+        database_url: "sqlite::memory:".into(),
+        jwt_secret: "test-secret".into(),
+        storage: Arc::new(storage::Local::new(&upload_dir)),
+        upload_dir,
+        site_base: SITE_BASE.into(),
+        custom_fields: None,
+        default_lang: "zh".into(),
+        admin_users: vec!["admin".into()],
+        sanitize: sanitize::Policy {
+            extra_tags: vec![],
+            extra_attrs: vec![],
+            url_schemes: vec!["http".into(), "https".into(), "mailto".into()],
+            embed_hosts: vec!["www.youtube.com".into()],
+            site_host: Some("blog.example.com".into()),
+        },
+        highlight_theme: crate::highlight::DEFAULT_THEME.into(),
+        markdown: markdown::Options::default(),
+        shortcodes: shortcodes::Registry::default(),
+        images: images::Settings { widths: vec![], formats: vec![], keep_exif: false },
+        upload_limits: media::Limits { image: 10 << 20, pdf: 10 << 20, audio: 10 << 20, request: 30 << 20 },
+    }
+}
+
+pub fn state(db: &Db) -> AppState {
+    let cfg = config();
+    AppState {
+        db: db.clone(),
+        jwt_secret: cfg.jwt_secret.clone(),
+        cfg,
+        rerender: Default::default(),
+        unlock_throttle: Default::default(),
+    }
+}
+
+/// 新用户，返回 id
+pub async fn user(db: &Db, username: &str) -> String {
+    let (id, ts) = (new_id(), now());
+    sqlx::query("INSERT INTO users (id, username, password_hash, created_at) VALUES (?,?,?,?)")
+        .bind(&id)
+        .bind(username)
+        .bind("-")
+        .bind(&ts)
+        .execute(db)
+        .await
+        .unwrap();
+    id
+}
+
+/// 按 PostInput 的 JSON 建文章（status 默认 published），返回 id
+pub async fn post(db: &Db, cfg: &Config, author_id: &str, input: serde_json::Value) -> Result<String, AppError> {
+    let mut input = input;
+    if input.get("status").is_none() {
+        input["status"] = "published".into();
+    }
+    let inp: PostInput = serde_json::from_value(input).unwrap();
+    let ts = Timestamps { published_at: Some(now()), ..Timestamps::now() };
+    let mut tx = db.begin().await?;
+    let (id, _, _) = posts::insert_post(&mut tx, cfg, author_id, &inp, &ts).await?;
+    tx.commit().await?;
+    Ok(id)
+}
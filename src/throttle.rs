@@ -0,0 +1,96 @@
+// 失败次数限制（进程内）：同一个 key 连续失败 FREE_FAILURES 次后开始退避，
+// 每多失败一次锁定时间翻倍（封顶 MAX_LOCK）；成功一次清零，长时间没有失败的记录会被清理
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const FREE_FAILURES: u32 = 5;
+const BASE_LOCK: Duration = Duration::from_secs(1);
+const MAX_LOCK: Duration = Duration::from_secs(15 * 60);
+// 超过这个时间没有失败就忘掉之前的失败
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+// 记录数超过这个值时顺便清理过期的
+const PRUNE_ABOVE: usize = 10_000;
+
+#[derive(Default)]
+pub struct Throttle {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+struct Entry {
+    failures: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl Throttle {
+    /// 还在锁定期内时返回剩余时间
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(key).and_then(|e| e.locked_until) {
+            Some(until) if until > Instant::now() => Err(until - Instant::now()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn failed(&self, key: &str) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() > PRUNE_ABOVE {
+            entries.retain(|_, e| now.duration_since(e.last) < FORGET_AFTER);
+        }
+        let e = entries
+            .entry(key.to_string())
+            .or_insert(Entry { failures: 0, last: now, locked_until: None });
+        if now.duration_since(e.last) >= FORGET_AFTER {
+            e.failures = 0;
+        }
+        e.failures += 1;
+        e.last = now;
+        if e.failures >= FREE_FAILURES {
+            let lock = BASE_LOCK.saturating_mul(1 << (e.failures - FREE_FAILURES).min(20)).min(MAX_LOCK);
+            e.locked_until = Some(now + lock);
+        }
+    }
+
+    pub fn succeeded(&self, key: &str) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locks_after_free_failures_and_resets_on_success() {
+        let t = Throttle::default();
+        for _ in 0..FREE_FAILURES - 1 {
+            t.failed("k");
+            assert!(t.check("k").is_ok());
+        }
+        t.failed("k");
+        assert!(t.check("k").is_err());
+        assert!(t.check("other").is_ok());
+
+        t.succeeded("k");
+        assert!(t.check("k").is_ok());
+    }
+
+    #[test]
+    fn lock_doubles_and_is_capped() {
+        let t = Throttle::default();
+        for _ in 0..FREE_FAILURES + 2 {
+            t.failed("k");
+        }
+        let left = t.check("k").unwrap_err();
+        assert!(left > BASE_LOCK * 2 && left <= BASE_LOCK * 4);
+
+        for _ in 0..100 {
+            t.failed("k");
+        }
+        assert!(t.check("k").unwrap_err() <= MAX_LOCK);
+    }
+}
@@ -0,0 +1,324 @@
+// WordPress 导出文件（WXR，RSS 2.0 + wp: 扩展）解析，以及文章 HTML → Markdown
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use roxmltree::{Document, Node};
+
+use crate::importer::normalize_date;
+
+const NS_WP_PREFIX: &str = "http://wordpress.org/export/"; // 1.0 / 1.1 / 1.2 都以此开头
+const NS_CONTENT: &str = "http://purl.org/rss/1.0/modules/content/";
+const NS_DC: &str = "http://purl.org/dc/elements/1.1/";
+
+pub struct Site {
+    pub link: String,
+    pub authors: Vec<Author>,
+    pub items: Vec<Item>,
+}
+
+pub struct Author {
+    pub login: String,
+    pub display_name: Option<String>,
+}
+
+pub struct Item {
+    pub id: String,
+    pub post_type: String, // post / page / attachment / nav_menu_item …
+    pub status: String,    // publish / draft / pending / future / private / trash …
+    pub title: String,
+    pub name: String, // 原 slug，可能是百分号编码
+    pub creator: String,
+    pub content: String,
+    pub excerpt: String,
+    pub password: String,
+    pub date: Option<String>,     // 已归一化为 UTC RFC3339
+    pub modified: Option<String>, // 同上
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+pub fn parse(xml: &str) -> anyhow::Result<Site> {
+    let doc = Document::parse(xml)?;
+    let Some(channel) = doc.descendants().find(|n| n.has_tag_name("channel")) else {
+        anyhow::bail!("not a WXR file: missing <channel>");
+    };
+
+    let authors = channel
+        .children()
+        .filter(|n| is_wp(n, "author"))
+        .filter_map(|a| {
+            Some(Author {
+                login: wp_text(a, "author_login").filter(|s| !s.is_empty())?,
+                display_name: wp_text(a, "author_display_name").filter(|s| !s.is_empty()),
+            })
+        })
+        .collect();
+
+    let items = channel
+        .children()
+        .filter(|n| n.has_tag_name("item"))
+        .map(|it| {
+            let (mut categories, mut tags) = (vec![], vec![]);
+            for c in it.children().filter(|n| n.has_tag_name("category")) {
+                let name = text(c).trim().to_string();
+                if name.is_empty() {
+                    continue;
+                }
+                match c.attribute("domain") {
+                    Some("category") if !categories.contains(&name) => categories.push(name),
+                    Some("post_tag") if !tags.contains(&name) => tags.push(name),
+                    _ => {}
+                }
+            }
+            Item {
+                id: wp_text(it, "post_id").unwrap_or_default(),
+                post_type: wp_text(it, "post_type").unwrap_or_else(|| "post".into()),
+                status: wp_text(it, "status").unwrap_or_else(|| "draft".into()),
+                title: child_text(it, "", "title").unwrap_or_default(),
+                name: wp_text(it, "post_name").unwrap_or_default(),
+                creator: child_text(it, NS_DC, "creator").unwrap_or_default(),
+                content: child_text(it, NS_CONTENT, "encoded").unwrap_or_default(),
+                excerpt: it
+                    .children()
+                    .find(|n| {
+                        n.tag_name().name() == "encoded"
+                            && n.tag_name().namespace().is_some_and(|ns| ns.ends_with("/excerpt/"))
+                    })
+                    .map(text)
+                    .unwrap_or_default(),
+                password: wp_text(it, "post_password").unwrap_or_default(),
+                date: wp_date(it, "post_date_gmt").or_else(|| wp_date(it, "post_date")),
+                modified: wp_date(it, "post_modified_gmt").or_else(|| wp_date(it, "post_modified")),
+                categories,
+                tags,
+            }
+        })
+        .filter(|it| !it.id.is_empty())
+        .collect();
+
+    Ok(Site {
+        link: child_text(channel, "", "link").unwrap_or_default(),
+        authors,
+        items,
+    })
+}
+
+fn text(n: Node) -> String {
+    n.descendants().filter(|d| d.is_text()).filter_map(|d| d.text()).collect()
+}
+
+fn child_text(n: Node, ns: &str, name: &str) -> Option<String> {
+    n.children()
+        .find(|c| c.tag_name().name() == name && c.tag_name().namespace().unwrap_or("") == ns)
+        .map(|c| text(c).trim().to_string())
+}
+
+fn is_wp(n: &Node, name: &str) -> bool {
+    n.tag_name().name() == name
+        && n.tag_name()
+            .namespace()
+            .is_some_and(|ns| ns.starts_with(NS_WP_PREFIX) && !ns.ends_with("/excerpt/"))
+}
+
+fn wp_text(n: Node, name: &str) -> Option<String> {
+    n.children().find(|c| is_wp(c, name)).map(|c| text(c).trim().to_string())
+}
+
+/// 草稿的 gmt 时间是 0000-00-00 00:00:00，视为没有
+fn wp_date(n: Node, name: &str) -> Option<String> {
+    wp_text(n, name)
+        .filter(|d| !d.starts_with("0000"))
+        .and_then(|d| normalize_date(&d))
+}
+
+static RE_COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+static RE_CAPTION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)\[caption[^\]]*\](.*?)\[/caption\]").unwrap());
+static RE_EMBED: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)\[embed[^\]]*\](.*?)\[/embed\]").unwrap());
+static RE_SHORTCODE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[(gallery|audio|video|playlist|wpvideo|contact-form[\w-]*)\b").unwrap());
+static RE_PRE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<pre\b([^>]*)>(.*?)</pre>").unwrap());
+static RE_HAS_P: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)<p[\s>]").unwrap());
+static RE_BLANK: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n[ \t]*\n").unwrap());
+static RE_BLOCK_START: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^<(?:p|div|h[1-6]|ul|ol|li|blockquote|table|figure|hr|pre|dl|section|iframe)\b")
+        .unwrap()
+});
+
+/// 文章 HTML → Markdown；返回正文和无法转换的短代码提示
+pub fn to_markdown(html: &str) -> (String, Vec<String>) {
+    let html = RE_COMMENT.replace_all(html, ""); // Gutenberg 块注释
+    let html = RE_CAPTION.replace_all(&html, "$1");
+    let html = RE_EMBED.replace_all(&html, |c: &Captures| {
+        let url = c[1].trim();
+        format!("<p><a href=\"{}\">{}</a></p>", url, url)
+    });
+
+    let warnings = RE_SHORTCODE
+        .captures_iter(&html)
+        .map(|c| format!("unsupported shortcode [{}] kept as text", &c[1]))
+        .collect();
+
+    // 经典编辑器 / SyntaxHighlighter 的 <pre> 里没有 <code>，补上才会转成代码块
+    let html = RE_PRE.replace_all(&html, |c: &Captures| {
+        if c[2].contains("<code") {
+            c[0].to_string()
+        } else {
+            format!("<pre{}><code>{}</code></pre>", &c[1], &c[2])
+        }
+    });
+    let html = if RE_HAS_P.is_match(&html) { html.into_owned() } else { autop(&html) };
+    let md = htmd::convert(&html).unwrap_or_else(|_| html.clone());
+    (md.trim().to_string(), warnings)
+}
+
+/// 经典编辑器存的是"空行分段"的 HTML 片段（WordPress 输出时才 wpautop），这里补上 <p>/<br>；<pre> 原样保留
+fn autop(html: &str) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for m in RE_PRE.find_iter(html) {
+        out.push_str(&autop_segment(&html[last..m.start()]));
+        out.push_str(m.as_str());
+        last = m.end();
+    }
+    out.push_str(&autop_segment(&html[last..]));
+    out
+}
+
+fn autop_segment(s: &str) -> String {
+    RE_BLANK
+        .split(&s.replace("\r\n", "\n"))
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(|b| {
+            if RE_BLOCK_START.is_match(b) {
+                b.to_string()
+            } else {
+                format!("<p>{}</p>", b.replace('\n', "<br>"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{importer, models::PostInput, posts, testing};
+
+    const WXR: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:excerpt="http://wordpress.org/export/1.2/excerpt/"
+     xmlns:content="http://purl.org/rss/1.0/modules/content/" xmlns:dc="http://purl.org/dc/elements/1.1/"
+     xmlns:wp="http://wordpress.org/export/1.2/">
+<channel>
+  <link>https://old.example.com/</link>
+  <wp:author><wp:author_login>alice</wp:author_login><wp:author_display_name>Alice</wp:author_display_name></wp:author>
+  <item>
+    <title>Hello TITLE</title>
+    <dc:creator>alice</dc:creator>
+    <content:encoded><![CDATA[<!-- wp:paragraph --><p>First <strong>post</strong>.</p><!-- /wp:paragraph -->]]></content:encoded>
+    <excerpt:encoded><![CDATA[Short]]></excerpt:encoded>
+    <wp:post_id>7</wp:post_id>
+    <wp:post_date_gmt>2020-01-02 03:04:05</wp:post_date_gmt>
+    <wp:post_name>hello-world</wp:post_name>
+    <wp:status>publish</wp:status>
+    <wp:post_type>post</wp:post_type>
+    <wp:post_password>secret</wp:post_password>
+    <category domain="post_tag" nicename="b"><![CDATA[B]]></category>
+    <category domain="category" nicename="a"><![CDATA[A]]></category>
+  </item>
+  <item>
+    <title>Classic</title>
+    <dc:creator>alice</dc:creator>
+    <content:encoded><![CDATA[one
+line
+
+<pre>code</pre>]]></content:encoded>
+    <wp:post_id>8</wp:post_id>
+    <wp:post_date_gmt>0000-00-00 00:00:00</wp:post_date_gmt>
+    <wp:post_name>classic</wp:post_name>
+    <wp:status>draft</wp:status>
+    <wp:post_type>post</wp:post_type>
+  </item>
+  <item><title>About</title><wp:post_id>9</wp:post_id><wp:post_type>page</wp:post_type></item>
+</channel>
+</rss>"#;
+
+    // 与 import_wxr 的映射一致（不含附件）
+    fn input(it: &Item) -> (PostInput, posts::Timestamps) {
+        let (status, visibility) = if it.status == "publish" { ("published", "public") } else { ("draft", "public") };
+        let visibility = if it.password.is_empty() { visibility } else { "protected" };
+        let mut tags = it.categories.clone();
+        tags.extend(it.tags.iter().cloned());
+        let created = it.date.clone().unwrap_or_else(|| "2024-01-01T00:00:00+00:00".into());
+        let inp = PostInput {
+            title: it.title.clone(),
+            slug: Some(posts::slugify(&it.name)),
+            body_md: to_markdown(&it.content).0,
+            tags: Some(tags),
+            status: Some(status.into()),
+            excerpt: Some(to_markdown(&it.excerpt).0).filter(|e| !e.is_empty()),
+            visibility: Some(visibility.into()),
+            password: Some(it.password.clone()).filter(|p| !p.is_empty()),
+            custom_fields: None,
+            lang: None,
+            translation_of: None,
+            markdown_options: None,
+        };
+        let ts = posts::Timestamps {
+            updated_at: it.modified.clone().unwrap_or_else(|| created.clone()),
+            published_at: (status == "published").then(|| created.clone()),
+            created_at: created,
+        };
+        (inp, ts)
+    }
+
+    #[test]
+    fn parses_items() {
+        let site = parse(WXR).unwrap();
+        assert_eq!(site.link, "https://old.example.com/");
+        assert_eq!(site.authors[0].display_name.as_deref(), Some("Alice"));
+        let ids: Vec<_> = site.items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["7", "8", "9"]);
+        let it = &site.items[0];
+        assert_eq!(it.categories, ["A"]);
+        assert_eq!(it.tags, ["B"]);
+        assert_eq!(it.date.as_deref(), Some("2020-01-02T03:04:05+00:00"));
+        assert_eq!(site.items[1].date, None);
+        assert_eq!(to_markdown(&it.content).0, "First **post**.");
+        assert_eq!(to_markdown(&site.items[1].content).0, "one  \nline\n\n```\ncode\n```");
+    }
+
+    #[tokio::test]
+    async fn rerun_is_idempotent() {
+        let db = testing::db().await;
+        let cfg = testing::config();
+        let alice = testing::user(&db, "alice").await;
+
+        // 第一次导入
+        let site = parse(WXR).unwrap();
+        let mut ids = vec![];
+        for it in site.items.iter().filter(|i| i.post_type == "post") {
+            let (inp, ts) = input(it);
+            ids.push(importer::create_post(&db, &cfg, &alice, &inp, &ts).await.unwrap());
+        }
+
+        // 再跑一次：同样的文件解析出的内容与库里一致（密码按哈希核对、标签不计顺序）
+        let site = parse(WXR).unwrap();
+        for (id, it) in ids.iter().zip(site.items.iter()) {
+            let (inp, _) = input(it);
+            let changes = importer::changes(&db, id, &inp).await.unwrap();
+            assert!(changes.is_empty(), "wp#{}: {:?}", it.id, changes);
+        }
+
+        // 原站改了标题和密码：只报这两项，覆盖后再比对又是一致的
+        let site = parse(&WXR.replace("Hello TITLE", "Hello again").replace(">secret<", ">changed<")).unwrap();
+        let (inp, ts) = input(&site.items[0]);
+        assert_eq!(importer::changes(&db, &ids[0], &inp).await.unwrap(), ["title", "password"]);
+        importer::overwrite_post(&db, &cfg, &ids[0], &inp, &ts).await.unwrap();
+        assert!(importer::changes(&db, &ids[0], &inp).await.unwrap().is_empty());
+        let n: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM posts").fetch_one(&db).await.unwrap();
+        assert_eq!(n, 2);
+    }
+}